@@ -1,3 +1,4 @@
+-- fresh databases only, existing ones are brought up to date with upgrade_db.sql
 CREATE SCHEMA IF NOT EXISTS constructum;
 
 CREATE TABLE constructum.repositories (
@@ -9,6 +10,15 @@ CREATE TABLE constructum.repositories (
     webhook_id INTEGER,
     enabled BOOLEAN NOT NULL,
     builds_executed INTEGER NOT NULL,
+    auto_cancel BOOLEAN NOT NULL DEFAULT FALSE,
+    debounce_seconds INTEGER NOT NULL DEFAULT 0,
+    max_concurrent_jobs INTEGER,
+    workspace_size TEXT,
+    storage_class TEXT,
+    -- share of the dispatch slots the repo gets relative to other repos with queued jobs
+    priority INTEGER NOT NULL DEFAULT 1,
+    -- Volume runs every step as a Job on a shared PVC, Pod runs all steps in the client pod on an emptyDir
+    workspace_mode TEXT NOT NULL DEFAULT 'Volume',
     CONSTRAINT valid_configuration CHECK (webhook_id IS NOT NULL OR enabled != TRUE)
 );
 
@@ -17,11 +27,33 @@ CREATE TABLE constructum.jobs (
     seq INTEGER NOT NULL,
     repo_id UUID REFERENCES constructum.repositories NOT NULL,
     commit_id TEXT NOT NULL,
+    git_ref TEXT,
+    trigger TEXT NOT NULL DEFAULT 'Push',
+    parameters JSONB NOT NULL DEFAULT '{}',
+    changed_files TEXT[],
+    parent_job UUID REFERENCES constructum.jobs,
+    rerun_of UUID REFERENCES constructum.jobs,
+    resume_from_step INTEGER,
+    workspace UUID,
+    -- minutes the pipeline asked to be allowed to run for
+    timeout_minutes INTEGER,
+    queued_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    not_before TIMESTAMPTZ,
+    -- uid of the PipelineRun resource that requested the job
+    pipeline_run UUID UNIQUE,
+    -- set when the job was moved to the front of the queue by hand
+    bumped_at TIMESTAMPTZ,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
     is_finished BOOLEAN NOT NULL,
     status TEXT NOT NULL,
+    -- why the job ended up in its status, when the server decided it
+    status_reason TEXT,
     UNIQUE (repo_id, seq)
 );
 
+CREATE INDEX jobs_queue ON constructum.jobs (queued_at) WHERE status = 'Queued';
+
 CREATE TABLE constructum.steps (
     id UUID PRIMARY KEY,
     job UUID REFERENCES constructum.jobs NOT NULL,
@@ -31,5 +63,37 @@ CREATE TABLE constructum.steps (
     commands TEXT[] NOT NULL,
     status TEXT NOT NULL,
     log_keys TEXT[] NOT NULL,
+    -- set when the step failed for something other than its commands, e.g. an image that cannot be pulled
+    failure_reason TEXT,
+    -- name@digest of every image a build_image step pushed
+    image_digests TEXT[],
+    -- attempts the cluster cut short before the one recorded above, e.g. by evicting the pod
+    interrupted_attempts JSONB NOT NULL DEFAULT '[]',
     UNIQUE (job, step_seq)
+);
+
+CREATE TABLE constructum.schedules (
+    id UUID PRIMARY KEY,
+    repo_id UUID REFERENCES constructum.repositories NOT NULL,
+    cron_expression TEXT NOT NULL,
+    branch TEXT NOT NULL,
+    parameters JSONB NOT NULL DEFAULT '{}',
+    enabled BOOLEAN NOT NULL,
+    next_run TIMESTAMPTZ NOT NULL,
+    last_run TIMESTAMPTZ,
+    last_job UUID REFERENCES constructum.jobs
+);
+
+CREATE TABLE constructum.webhook_deliveries (
+    id UUID PRIMARY KEY,
+    delivery_id TEXT UNIQUE NOT NULL,
+    repo_id UUID REFERENCES constructum.repositories,
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    job_id UUID REFERENCES constructum.jobs,
+    error TEXT,
+    redelivery_of UUID REFERENCES constructum.webhook_deliveries,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- NULL while an attempt is still processing the delivery
+    completed_at TIMESTAMPTZ
 );
\ No newline at end of file
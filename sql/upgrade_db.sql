@@ -0,0 +1,75 @@
+-- brings a database created from an older create_db.sql up to date.
+-- every statement is idempotent, so this is safe to run against a database at any earlier version.
+BEGIN;
+
+ALTER TABLE constructum.repositories
+    ADD COLUMN IF NOT EXISTS auto_cancel BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS debounce_seconds INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS max_concurrent_jobs INTEGER,
+    ADD COLUMN IF NOT EXISTS workspace_size TEXT,
+    ADD COLUMN IF NOT EXISTS storage_class TEXT,
+    ADD COLUMN IF NOT EXISTS priority INTEGER NOT NULL DEFAULT 1,
+    ADD COLUMN IF NOT EXISTS workspace_mode TEXT NOT NULL DEFAULT 'Volume';
+
+ALTER TABLE constructum.jobs
+    ADD COLUMN IF NOT EXISTS git_ref TEXT,
+    ADD COLUMN IF NOT EXISTS trigger TEXT NOT NULL DEFAULT 'Push',
+    ADD COLUMN IF NOT EXISTS parameters JSONB NOT NULL DEFAULT '{}',
+    ADD COLUMN IF NOT EXISTS changed_files TEXT[],
+    ADD COLUMN IF NOT EXISTS parent_job UUID REFERENCES constructum.jobs,
+    ADD COLUMN IF NOT EXISTS rerun_of UUID REFERENCES constructum.jobs,
+    ADD COLUMN IF NOT EXISTS resume_from_step INTEGER,
+    ADD COLUMN IF NOT EXISTS workspace UUID,
+    ADD COLUMN IF NOT EXISTS timeout_minutes INTEGER,
+    ADD COLUMN IF NOT EXISTS queued_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN IF NOT EXISTS not_before TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS pipeline_run UUID UNIQUE,
+    ADD COLUMN IF NOT EXISTS bumped_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS started_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS finished_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS status_reason TEXT;
+
+CREATE INDEX IF NOT EXISTS jobs_queue ON constructum.jobs (queued_at) WHERE status = 'Queued';
+
+ALTER TABLE constructum.steps
+    ADD COLUMN IF NOT EXISTS failure_reason TEXT,
+    ADD COLUMN IF NOT EXISTS image_digests TEXT[],
+    ADD COLUMN IF NOT EXISTS interrupted_attempts JSONB NOT NULL DEFAULT '[]';
+
+CREATE TABLE IF NOT EXISTS constructum.schedules (
+    id UUID PRIMARY KEY,
+    repo_id UUID REFERENCES constructum.repositories NOT NULL,
+    cron_expression TEXT NOT NULL,
+    branch TEXT NOT NULL,
+    parameters JSONB NOT NULL DEFAULT '{}',
+    enabled BOOLEAN NOT NULL,
+    next_run TIMESTAMPTZ NOT NULL,
+    last_run TIMESTAMPTZ,
+    last_job UUID REFERENCES constructum.jobs
+);
+
+CREATE TABLE IF NOT EXISTS constructum.webhook_deliveries (
+    id UUID PRIMARY KEY,
+    delivery_id TEXT UNIQUE NOT NULL,
+    repo_id UUID REFERENCES constructum.repositories,
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    job_id UUID REFERENCES constructum.jobs,
+    error TEXT,
+    redelivery_of UUID REFERENCES constructum.webhook_deliveries,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- deliveries recorded before completed_at existed are all done, none of them is still being processed
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_schema = 'constructum' AND table_name = 'webhook_deliveries' AND column_name = 'completed_at'
+    ) THEN
+        ALTER TABLE constructum.webhook_deliveries ADD COLUMN completed_at TIMESTAMPTZ;
+        UPDATE constructum.webhook_deliveries SET completed_at = received_at;
+    END IF;
+END $$;
+
+COMMIT;
@@ -1,15 +1,34 @@
-
 use axum::{
     routing::{get}, Router,
 };
-use constructum::{client::{create_client_job, ConstructumClientError}, config::Config};
+use constructum::{client::{create_client_job, run_local_pipeline, ConstructumClientError, LocalRunOptions}, config::Config, pipeline::PipelineStatus};
+
+use std::{collections::HashMap, net::SocketAddr, path::PathBuf, process::ExitCode};
 
-use std::net::SocketAddr;
+const USAGE: &str = "usage: constructum-client run --local [--step NAME] [--secrets FILE] [--param NAME=VALUE]... [--docker]";
 
 #[tokio::main]
-async fn main() -> Result<(), ConstructumClientError> {
+async fn main() -> Result<ExitCode, ConstructumClientError> {
     tracing_subscriber::fmt::init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        let options = match parse_run_args(&args) {
+            Ok(options) => options,
+            Err(err) => {
+                eprintln!("{err}\n{USAGE}");
+                return Ok(ExitCode::from(2));
+            }
+        };
+
+        let pipeline_status = run_local_pipeline(options).await?;
+        println!("{pipeline_status:?}");
+        return match pipeline_status {
+            PipelineStatus::Complete => Ok(ExitCode::SUCCESS),
+            _ => Ok(ExitCode::FAILURE),
+        };
+    }
+
     let config = match envy::prefixed("CONSTRUCTUM_").from_env::<Config>() {
         Ok(cfg) => cfg,
         Err(err) => panic!("{err:#?}"),
@@ -30,5 +49,49 @@ async fn main() -> Result<(), ConstructumClientError> {
         }
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+fn parse_run_args(args: &[String]) -> Result<LocalRunOptions, String> {
+    let mut args = args.iter();
+    if args.next().map(String::as_str) != Some("run") {
+        return Err(String::from("unknown command"));
+    }
+
+    let working_directory = std::env::current_dir().map_err(|err| format!("failed to read the current directory: {err}"))?;
+    let mut options = LocalRunOptions {
+        secrets_file: working_directory.join(".constructum.env"),
+        working_directory,
+        step: None,
+        parameters: HashMap::new(),
+        use_containers: false,
+    };
+    let mut local = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--local" => local = true,
+            "--docker" => options.use_containers = true,
+            "--step" => options.step = Some(args.next().ok_or("--step needs a step name")?.clone()),
+            "--secrets" => options.secrets_file = PathBuf::from(args.next().ok_or("--secrets needs a file")?),
+            "--param" => {
+                let (name, value) = args.next().and_then(|x| x.split_once('=')).ok_or("--param needs NAME=VALUE")?;
+                // booleans are given as true/false, everything else is taken as a string
+                let value = match value {
+                    "true" => serde_json::Value::Bool(true),
+                    "false" => serde_json::Value::Bool(false),
+                    _ => serde_json::Value::String(value.to_owned()),
+                };
+                options.parameters.insert(name.to_owned(), value);
+            },
+            _ => return Err(format!("unknown argument {arg}")),
+        }
+    }
+
+    // runs against the cluster are started by the server, so only local runs can be started from here
+    if !local {
+        return Err(String::from("only --local runs can be started from the command line"));
+    }
+
+    Ok(options)
 }
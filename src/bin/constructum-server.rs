@@ -11,13 +11,21 @@ use tower_http::{
     classify::StatusInRangeAsFailures, normalize_path::NormalizePathLayer, trace::TraceLayer,
 };
 use tower_layer::Layer;
+use tokio_cron_scheduler::{Job, JobScheduler};
 
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::Duration};
 
 #[tokio::main]
 async fn main() -> Result<(), ConstructumConfigError> {
     tracing_subscriber::fmt::init();
-    // let sched = JobScheduler::new().await.expect("failed to make scheduler");
+
+    // lets the CRD be installed with `constructum-server --print-crd | kubectl apply -f -`
+    if std::env::args().any(|x| x == "--print-crd") {
+        println!("{}", serde_yaml::to_string(&constructum::server::pipeline_run_crd()).expect("failed to serialize CRD"));
+        return Ok(());
+    }
+
+    let sched = JobScheduler::new().await.expect("failed to make scheduler");
 
     let config = match envy::prefixed("CONSTRUCTUM_").from_env::<Config>() {
         Ok(cfg) => cfg,
@@ -32,6 +40,7 @@ async fn main() -> Result<(), ConstructumConfigError> {
     let subrouter = constructum::server::api::webhook::register_module(subrouter);
     let subrouter = constructum::server::api::job::register_module(subrouter);
     let subrouter = constructum::server::api::repo::register_module(subrouter);
+    let subrouter = constructum::server::api::schedule::register_module(subrouter);
 
     let app = NormalizePathLayer::trim_trailing_slash().layer(
         Router::new()
@@ -46,15 +55,59 @@ async fn main() -> Result<(), ConstructumConfigError> {
     let addr = SocketAddr::from(([0, 0, 0, 0], 3001));
     tracing::debug!("listening on {}", addr);
 
-    // sched.add(Job::new_repeated_async(Duration::from_secs(300), move |_uuid, _l| {
-    //     let cloned_state = state.clone();
-    //     Box::pin(async move {
-    //         println!("restarting an unfinished job");
-    //         restart_unfinished_jobs(cloned_state).await.expect("Failed to restart unfinished jobs");
-    //     })
-    // }).expect("failed to build job")).await.expect("failed to schedule job");
+    let schedule_state = state.clone();
+    sched.add(Job::new_repeated_async(Duration::from_secs(constructum::server::SCHEDULE_POLL_INTERVAL_SECS), move |_uuid, _l| {
+        let cloned_state = schedule_state.clone();
+        Box::pin(async move {
+            if let Err(err) = constructum::server::run_due_schedules(cloned_state).await {
+                tracing::error!("Failed to run due schedules: {err}");
+            }
+        })
+    }).expect("failed to build job")).await.expect("failed to schedule job");
+
+    let queue_state = state.clone();
+    sched.add(Job::new_repeated_async(Duration::from_secs(constructum::server::QUEUE_POLL_INTERVAL_SECS), move |_uuid, _l| {
+        let cloned_state = queue_state.clone();
+        Box::pin(async move {
+            if let Err(err) = constructum::server::dispatch_queued_jobs(cloned_state).await {
+                tracing::error!("Failed to dispatch queued jobs: {err}");
+            }
+        })
+    }).expect("failed to build job")).await.expect("failed to schedule job");
+
+    // pick up whatever the previous server process left behind before taking new work.
+    // with leader election this happens once the lease is acquired instead.
+    if state.leader_election() {
+        tokio::spawn(constructum::server::run_leader_election(state.clone()));
+    } else if let Err(err) = constructum::server::reconcile_jobs(state.clone()).await {
+        tracing::error!("Failed to reconcile jobs: {err}");
+    }
+
+    if state.pipeline_run_controller() {
+        tokio::spawn(constructum::server::run_pipeline_run_controller(state.clone()));
+    }
+
+    let reconcile_state = state.clone();
+    sched.add(Job::new_repeated_async(Duration::from_secs(constructum::server::RECONCILE_INTERVAL_SECS), move |_uuid, _l| {
+        let cloned_state = reconcile_state.clone();
+        Box::pin(async move {
+            if let Err(err) = constructum::server::reconcile_jobs(cloned_state).await {
+                tracing::error!("Failed to reconcile jobs: {err}");
+            }
+        })
+    }).expect("failed to build job")).await.expect("failed to schedule job");
+
+    let watchdog_state = state.clone();
+    sched.add(Job::new_repeated_async(Duration::from_secs(constructum::server::WATCHDOG_INTERVAL_SECS), move |_uuid, _l| {
+        let cloned_state = watchdog_state.clone();
+        Box::pin(async move {
+            if let Err(err) = constructum::server::time_out_expired_jobs(cloned_state).await {
+                tracing::error!("Failed to time out expired jobs: {err}");
+            }
+        })
+    }).expect("failed to build job")).await.expect("failed to schedule job");
 
-    // sched.start().await.expect("failed to start scheduler");
+    sched.start().await.expect("failed to start scheduler");
 
     axum::Server::bind(&addr)
         .serve(app.into_make_service())
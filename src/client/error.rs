@@ -1,6 +1,6 @@
 use std::{error::Error, fmt::Display};
 
-use crate::{config::ConstructumConfigError, git::GitError, redis::error::ConstructumRedisError, kube::error::ConstructumKubeError};
+use crate::{config::ConstructumConfigError, git::GitError, redis::error::ConstructumRedisError, kube::error::ConstructumKubeError, pipeline::PipelineParameterError};
 
 #[derive(Debug)]
 pub enum ConstructumClientError {
@@ -10,6 +10,8 @@ pub enum ConstructumClientError {
     YamlDecodeError(serde_yaml::Error),
     PipelineExecError(PipelineExecError),
     GitError(GitError),
+    ParameterError(PipelineParameterError),
+    UnknownStep(String),
 }
 
 impl Display for ConstructumClientError {
@@ -21,6 +23,8 @@ impl Display for ConstructumClientError {
             ConstructumClientError::YamlDecodeError(yamle) => write!(f, "Client Error: Yaml Decode Error: {yamle}"),
             ConstructumClientError::PipelineExecError(pipee) => write!(f, "Client Error: Pipeline Error: {pipee}"),
             ConstructumClientError::GitError(gite) => write!(f, "Client Error: Config Error: {gite}"),
+            ConstructumClientError::ParameterError(parame) => write!(f, "Client Error: Parameter Error: {parame}"),
+            ConstructumClientError::UnknownStep(step) => write!(f, "Client Error: No step named {step} in the pipeline"),
         }
     }
 }
@@ -63,6 +67,12 @@ impl From<GitError> for ConstructumClientError {
     }
 }
 
+impl From<PipelineParameterError> for ConstructumClientError {
+    fn from(value: PipelineParameterError) -> Self {
+        ConstructumClientError::ParameterError(value)
+    }
+}
+
 #[derive(Debug)]
 pub enum PipelineExecError {
     KubernetesError(kube::Error),
@@ -74,6 +84,9 @@ pub enum PipelineExecError {
     IOError(std::io::Error),
     RedisError(ConstructumRedisError),
     ConstructumKube(ConstructumKubeError),
+    InvalidPathFilter(globset::Error),
+    GitError(GitError),
+    UnsupportedStep(String),
 }
 
 impl Display for PipelineExecError {
@@ -88,6 +101,9 @@ impl Display for PipelineExecError {
             PipelineExecError::IOError(ioe) => write!(f, "Pipeline Error: I/O Error Error: {ioe}"),
             PipelineExecError::RedisError(red) => write!(f, "Pipeline Error: Redis Error: {red}"),
             PipelineExecError::ConstructumKube(kubc) => write!(f, "Pipeline Error: Kube Error: {kubc}"),
+            PipelineExecError::InvalidPathFilter(glob) => write!(f, "Pipeline Error: Invalid Path Filter: {glob}"),
+            PipelineExecError::GitError(gite) => write!(f, "Pipeline Error: Git Error: {gite}"),
+            PipelineExecError::UnsupportedStep(reason) => write!(f, "Pipeline Error: Unsupported Step: {reason}"),
         }
     }
 }
@@ -140,4 +156,16 @@ impl From<ConstructumKubeError> for PipelineExecError {
     fn from(value: ConstructumKubeError) -> Self {
         PipelineExecError::ConstructumKube(value)
     }
+}
+
+impl From<globset::Error> for PipelineExecError {
+    fn from(value: globset::Error) -> Self {
+        PipelineExecError::InvalidPathFilter(value)
+    }
+}
+
+impl From<GitError> for PipelineExecError {
+    fn from(value: GitError) -> Self {
+        PipelineExecError::GitError(value)
+    }
 }
\ No newline at end of file
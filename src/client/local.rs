@@ -0,0 +1,89 @@
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}};
+
+use uuid::Uuid;
+
+use crate::{executor::{LocalExecutor, StepExecutor}, pipeline::{MaterializedSecretConfig, Pipeline, PipelineStatus}};
+
+use super::{execute_pipeline, materialize_pipeline_secrets, ConsoleRecorder, ConstructumClientError, PipelineExecError, PipelineRun};
+
+pub struct LocalRunOptions {
+    // checkout holding the .constructum.yml to run
+    pub working_directory: PathBuf,
+    // only run the step with this name
+    pub step: Option<String>,
+    // KEY=VALUE lines, keyed by the variable a step sees the secret in
+    pub secrets_file: PathBuf,
+    pub parameters: HashMap<String, serde_json::Value>,
+    // run steps in their image through docker rather than as plain processes
+    pub use_containers: bool,
+}
+
+// runs a pipeline on this machine the way the client job would, minus postgres, s3 and redis
+pub async fn run_local_pipeline(options: LocalRunOptions) -> Result<PipelineStatus, ConstructumClientError> {
+    let pipeline_contents = tokio::fs::read_to_string(options.working_directory.join(".constructum.yml")).await?;
+    let mut pipeline: Pipeline = serde_yaml::from_str(&pipeline_contents)?;
+    pipeline.normalize();
+
+    if let Some(step_name) = &options.step {
+        pipeline.steps.retain(|x| &x.name == step_name);
+        if pipeline.steps.is_empty() {
+            return Err(ConstructumClientError::UnknownStep(step_name.clone()));
+        }
+    }
+
+    let parameters = pipeline.resolve_parameters(&options.parameters, true)?;
+    let materialized_secrets = materialize_pipeline_secrets(&pipeline)?;
+    let secrets = read_local_secrets(&pipeline, &materialized_secrets, &options.secrets_file).await?;
+
+    let pipeline_uuid = Uuid::new_v4();
+    let log_directory = std::env::temp_dir().join(format!("constructum-{pipeline_uuid}"));
+    let mut executor = LocalExecutor::new(&options.working_directory, &log_directory, secrets, options.use_containers);
+    let pipeline_working_directory = executor.prepare_workspace().await?;
+
+    let run = PipelineRun {
+        pipeline_uuid,
+        workspace_uuid: pipeline_uuid,
+        parameters,
+        changed_files: None,
+    };
+
+    let pipeline_status = execute_pipeline(&mut executor, &mut ConsoleRecorder::default(), pipeline, &run, pipeline_working_directory, materialized_secrets, Vec::new()).await?;
+    Ok(pipeline_status)
+}
+
+// stands in for vault: every secret a step of this run uses has to be in the env file
+async fn read_local_secrets(pipeline: &Pipeline, materialized_secrets: &MaterializedSecretConfig, secrets_file: &Path) -> Result<HashMap<String, String>, PipelineExecError> {
+    let used: HashSet<String> = pipeline.steps.iter()
+        .flat_map(|x| x.secrets.clone().unwrap_or_default())
+        .map(|x| x.name)
+        .collect();
+
+    if used.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let contents = tokio::fs::read_to_string(secrets_file).await?;
+    let values = parse_env_file(&contents);
+
+    for secret in materialized_secrets.secrets().iter().filter(|x| used.contains(&x.object_name)) {
+        if !values.contains_key(&secret.object_name.to_uppercase()) {
+            // FAIL! secret not found!
+            return Err(PipelineExecError::InvalidSecretConfiguration);
+        }
+    }
+
+    Ok(values)
+}
+
+pub(crate) fn parse_env_file(contents: &str) -> HashMap<String, String> {
+    contents.lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .filter_map(|x| x.strip_prefix("export ").unwrap_or(x).split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let value = value.strip_prefix('"').and_then(|x| x.strip_suffix('"')).unwrap_or(value);
+            (key.trim().to_owned(), value.to_owned())
+        })
+        .collect()
+}
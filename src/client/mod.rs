@@ -1,234 +1,318 @@
 use std::{path::{Path, PathBuf}, str::FromStr, collections::HashMap};
 
 
-use k8s_openapi::api::batch::v1::Job;
-use kube::{Api, api::PostParams, runtime::wait::conditions};
-
+use chrono::Utc;
 use serde::{Serialize, Deserialize};
 
-use tokio::{io::AsyncReadExt};
 use uuid::Uuid;
 
-use crate::{pipeline::{Pipeline, PipelineStatus, PipelineJobConfig, MaterializedSecretConfig, PipelineStep, MaterializedSecret}, config::Config, git, kube::{put_pod_logs_to_s3, delete_job}, server::{api::{job::{db::{get_job, complete_job}, JobInfo}, self, repo::RepoInfo, step::model::StepStatus}, self}, utils, ConstructumClientState, redis::logs_to_redis};
+use crate::{pipeline::{Pipeline, PipelineStatus, PipelineJobConfig, MaterializedSecretConfig, PipelineStep, MaterializedSecret}, config::Config, executor::{KubernetesExecutor, PodExecutor, StepExecutor, StepOutcome}, server::{api::{job::{db::{get_job, complete_job}, JobInfo}, repo::{RepoInfo, WorkspaceMode}, step::model::{CompletedPipelineStep, StepAttempt, StepStatus}}, self}, utils, ConstructumClientState};
 
 mod error;
+mod local;
+mod recorder;
 
 #[cfg(test)]
 mod tests;
 
 pub use self::error::*;
+pub use self::local::*;
+pub use self::recorder::*;
+
+// what execute_pipeline needs to know about a run, whether or not the server started it
+pub struct PipelineRun {
+    pub pipeline_uuid: Uuid,
+    // uuid of the job whose workspace the steps use
+    pub workspace_uuid: Uuid,
+    pub parameters: HashMap<String, String>,
+    pub changed_files: Option<Vec<String>>,
+}
+
+impl From<&JobInfo> for PipelineRun {
+    fn from(value: &JobInfo) -> Self {
+        PipelineRun {
+            pipeline_uuid: value.job_uuid,
+            workspace_uuid: value.workspace_uuid(),
+            parameters: value.parameters.clone(),
+            changed_files: value.changed_files.clone(),
+        }
+    }
+}
 
 pub async fn create_client_job(config: Config) -> Result<(), ConstructumClientError> {
     let pipeline_uuid = Uuid::from_str(config.pipeline_uuid.as_ref().expect("failed to get pipeline ID")).expect("failed to coerce to UUID");
     let vault_url = config.vault_server.clone().expect("failed to acquire vault server URL for client");
     let k8s_token = super::kube::read_kubernetes_token(vault_url.clone(), PathBuf::from("/var/run/secrets/tokens/vault-token")).await?;
+    let pod_name = config.pod_name.clone();
     
     let state = ConstructumClientState::new(config).await?;
 
     let pipeline_info: JobInfo = get_job(pipeline_uuid, state.postgres()).await?;
     let repo_info: RepoInfo = server::api::repo::db::get_repo(pipeline_info.repo_id, state.postgres()).await?;
+
+    let pipeline_status = match pipeline_info.workspace_mode(&repo_info) {
+        WorkspaceMode::Volume => {
+            let mut executor = KubernetesExecutor::new(Path::new("/data/"), &pipeline_info, &repo_info, state.redis(), state.s3_bucket(), state.kube_settings().clone()).await?;
+            run_client_pipeline(&mut executor, &pipeline_info, &state, vault_url, k8s_token).await?
+        },
+        WorkspaceMode::Pod => {
+            let pod_name = pod_name.expect("failed to find the name of the client pod");
+            let mut executor = PodExecutor::new(Path::new("/data/"), pod_name, &pipeline_info, &repo_info, state.redis(), state.s3_bucket(), state.kube_settings().clone()).await?;
+            run_client_pipeline(&mut executor, &pipeline_info, &state, vault_url, k8s_token).await?
+        },
+    };
+    println!("{pipeline_status:?}");
+
+    complete_job(state.postgres(), pipeline_status, pipeline_uuid).await?;
+
+    Ok(())
+}
+
+async fn run_client_pipeline<E: StepExecutor>(executor: &mut E, pipeline_info: &JobInfo, state: &ConstructumClientState, vault_url: String, k8s_token: String) -> Result<PipelineStatus, ConstructumClientError> {
     // begin by initializing the workspace for future jobs
-    let pipeline_file = git::pull_repository(Path::new("/data/"), repo_info.repo_url, repo_info.repo_name, pipeline_info.commit_id).await?;
-    let pipeline_working_directory = pipeline_file.1;
-    let mut pipeline_file = pipeline_file.0;
-    let mut pipeline_contents = String::new();
-    pipeline_file.read_to_string(&mut pipeline_contents).await?;
+    let pipeline_working_directory = executor.prepare_workspace().await?;
+    let pipeline_contents = tokio::fs::read_to_string(pipeline_working_directory.join(".constructum.yml")).await?;
 
     let mut pipeline: Pipeline = serde_yaml::from_str(&pipeline_contents)?;
     pipeline.normalize();
-    println!("{pipeline:?}");
 
-    let materialized_secrets = build_pipeline_secrets(pipeline.clone(), vault_url.clone(), k8s_token).await?;
+    let materialized_secrets = build_pipeline_secrets(pipeline.clone(), vault_url, k8s_token).await?;
 
-    let pipeline_status = execute_pipeline(pipeline.clone(), pipeline_info.job_uuid, pipeline_working_directory, &state, materialized_secrets).await?;
-    println!("{pipeline_status:?}");
+    // a resumed job carries over the outcomes of every step before the one that failed
+    let reused_steps = match (pipeline_info.rerun_of, pipeline_info.resume_from_step) {
+        (Some(original), Some(resume_from_step)) => server::api::step::db::list_steps_for_job(state.postgres(), original)
+            .await?
+            .into_iter()
+            .filter(|x| x.step_number < resume_from_step)
+            .collect(),
+        _ => Vec::new(),
+    };
 
-    complete_job(state.postgres(), pipeline_status, pipeline_uuid).await?;
-
-    Ok(())
+    let mut recorder = PostgresRecorder::new(state.postgres(), pipeline_info.job_uuid);
+    Ok(execute_pipeline(executor, &mut recorder, pipeline, &PipelineRun::from(pipeline_info), pipeline_working_directory, materialized_secrets, reused_steps).await?)
 }
 
 pub async fn build_pipeline_secrets(pipeline: Pipeline, vault_url: String, token: String) -> Result<MaterializedSecretConfig, PipelineExecError> {
-    let secrets_requested = pipeline.secrets;
-
-    match secrets_requested {
-        Some(secrets) => {
-            for (idx, secret) in secrets.iter().enumerate() {
-                let mut second = secrets.clone();
-                second.remove(idx);
-                for secret_two in second {
-                    if secret.name == secret_two.name {
-                        // FAIL! cannot have 2 secrets with the same name.
-                        return Err(PipelineExecError::InvalidSecretConfiguration);
-                    }
-                }
-            }
+    let materialized_secrets = materialize_pipeline_secrets(&pipeline)?;
 
-            // validating all secrets exist
-            for secret in secrets.iter() {
+    // validating all secrets exist
+    for secret in materialized_secrets.secrets().iter() {
 
-                #[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
-                struct VaultSecretMetadata {
-                    subkeys: HashMap<String, Option<String>>,
-                }
+        #[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+        struct VaultSecretMetadata {
+            subkeys: HashMap<String, Option<String>>,
+        }
 
-                #[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
-                struct VaultSecretResp {
-                    data: VaultSecretMetadata
-                }
+        #[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+        struct VaultSecretResp {
+            data: VaultSecretMetadata
+        }
 
 
-                let resp = {
-                    utils::get_with_auth(format!("{vault_url}/v1/constructum/subkeys/{}", secret.location), "X-Vault-Token", token.clone()).await?
-                };
-            
-                let md = resp.json::<VaultSecretResp>().await?;
+        let resp = {
+            utils::get_with_auth(format!("{vault_url}/v1/constructum/subkeys/{}", secret.secret_path), "X-Vault-Token", token.clone()).await?
+        };
+    
+        let md = resp.json::<VaultSecretResp>().await?;
 
-                if !md.data.subkeys.contains_key(&secret.key) {
-                    // FAIL! secret not found!
-                    return Err(PipelineExecError::InvalidSecretConfiguration);
-                }
+        if !md.data.subkeys.contains_key(&secret.secret_key) {
+            // FAIL! secret not found!
+            return Err(PipelineExecError::InvalidSecretConfiguration);
+        }
 
-            }
+    }
 
-            let materialized_secrets: Vec<MaterializedSecret> = secrets.into_iter().map(|x| MaterializedSecret::new(x.name, x.location, x.key)).collect();
+    Ok(materialized_secrets)
+}
 
-            // validating all secrets in all steps exist within our materialized secrets
-            for ss in pipeline.steps.iter().flat_map(|x| x.secrets.clone().unwrap_or(Vec::new())) {
-                if materialized_secrets.iter().filter(|x| x.object_name == ss.name).count() == 0 {
-                    // FAIL! secret does not exist for pipeline step.
-                    return Err(PipelineExecError::InvalidSecretConfiguration);
-                }
+// checks the secrets a pipeline declares and uses against each other, wherever their values come from
+pub(crate) fn materialize_pipeline_secrets(pipeline: &Pipeline) -> Result<MaterializedSecretConfig, PipelineExecError> {
+    let secrets = match &pipeline.secrets {
+        Some(secrets) => secrets.clone(),
+        None => return Ok(MaterializedSecretConfig::default()),
+    };
+
+    for (idx, secret) in secrets.iter().enumerate() {
+        let mut second = secrets.clone();
+        second.remove(idx);
+        for secret_two in second {
+            if secret.name == secret_two.name {
+                // FAIL! cannot have 2 secrets with the same name.
+                return Err(PipelineExecError::InvalidSecretConfiguration);
             }
+        }
+    }
 
-            Ok(MaterializedSecretConfig::new(materialized_secrets))
-        },
-        None => Ok(MaterializedSecretConfig::default()),
+    let materialized_secrets: Vec<MaterializedSecret> = secrets.into_iter().map(|x| MaterializedSecret::new(x.name, x.location, x.key)).collect();
+
+    // validating all secrets in all steps exist within our materialized secrets
+    for name in pipeline.steps.iter().flat_map(|x| x.secret_names()) {
+        if materialized_secrets.iter().filter(|x| x.object_name == name).count() == 0 {
+            // FAIL! secret does not exist for pipeline step.
+            return Err(PipelineExecError::InvalidSecretConfiguration);
+        }
     }
+
+    Ok(MaterializedSecretConfig::new(materialized_secrets))
 }
 
 pub async fn build_step_secrets(step: PipelineStep, pipeline_secret_config: MaterializedSecretConfig) -> Result<Option<crate::kube::VaultAnnotations>, PipelineExecError> {
     let secrets_requested = step.secrets;
+    let registry_secret = step.build_image.and_then(|x| x.registry_secret);
     let global_indexed_secrets = pipeline_secret_config.indexed_secrets();
 
-    match secrets_requested {
-        Some(step_secrets) => {
-            let mut mat_secs = Vec::new();
-            for ss in step_secrets {
-                match global_indexed_secrets.get(&ss.name) {
-                    Some(mat_sec) => mat_secs.push(mat_sec.clone()),
-                    // should not happen, secrets should have been validated earlier
-                    None => return Err(PipelineExecError::InvalidSecretConfiguration)
-                }
-            }
+    if secrets_requested.is_none() && registry_secret.is_none() {
+        return Ok(None);
+    }
 
-            let job_mat_secrets = MaterializedSecretConfig::new(mat_secs);
+    let mut mat_secs = Vec::new();
+    for ss in secrets_requested.unwrap_or_default() {
+        match global_indexed_secrets.get(&ss.name) {
+            Some(mat_sec) => mat_secs.push(mat_sec.clone()),
+            // should not happen, secrets should have been validated earlier
+            None => return Err(PipelineExecError::InvalidSecretConfiguration)
+        }
+    }
 
-            let spc = crate::kube::build_vault_annotations(job_mat_secrets);
+    let job_mat_secrets = MaterializedSecretConfig::new(mat_secs);
 
-            Ok(Some(spc))
-        },
-        None => Ok(None),
+    let mut spc = crate::kube::build_vault_annotations(job_mat_secrets);
+
+    if let Some(registry_secret) = registry_secret {
+        match global_indexed_secrets.get(&registry_secret) {
+            Some(mat_sec) => spc = spc.with_docker_config(mat_sec.clone()),
+            None => return Err(PipelineExecError::InvalidSecretConfiguration)
+        }
     }
+
+    Ok(Some(spc))
 }
 
-pub async fn execute_pipeline(pipeline: Pipeline, pipeline_uuid: uuid::Uuid, pipeline_working_directory: PathBuf, state: &ConstructumClientState, secrets: MaterializedSecretConfig) -> Result<PipelineStatus, PipelineExecError> {        
+pub async fn execute_pipeline<E: StepExecutor, R: StepRecorder>(executor: &mut E, recorder: &mut R, pipeline: Pipeline, run: &PipelineRun, pipeline_working_directory: PathBuf, secrets: MaterializedSecretConfig, reused_steps: Vec<CompletedPipelineStep>) -> Result<PipelineStatus, PipelineExecError> {
         // read in stages
-        let k8s_client = kube::Client::try_default().await?;
-        // execute stages as jobs on k8s
 
         let mut steps = Vec::new();
         for (step_num, step) in pipeline.steps.clone().into_iter().enumerate() {
-            let step_uuid = api::step::db::insert_step(state.postgres(), pipeline_uuid, i32::try_from(step_num).expect("failed to convert step num"), &step).await?;
+            let step_uuid = recorder.insert_step(i32::try_from(step_num).expect("failed to convert step num"), &step).await?;
             steps.push((step_uuid, step));
         }
 
-    
-        let jobs: Api<Job> = Api::namespaced(k8s_client.clone(), "constructum");
-        for (step_id, step) in steps {
-            let name = step.name.clone();
-            api::step::db::update_step_status(state.postgres(), step_id, StepStatus::InProgress).await?;
 
-            // grab all secrets necessary for this step
+        for (step_num, (step_id, step)) in steps.into_iter().enumerate() {
+            let step_number = i32::try_from(step_num).expect("failed to convert step num");
+            let name = step.name.clone();
 
-            let secrets_generated = build_step_secrets(step.clone(), secrets.clone()).await?;
+            // the server marks the remaining steps when it cancels the job
+            if recorder.is_cancelled().await? {
+                return Ok(PipelineStatus::Cancelled);
+            }
 
-            // build corrected argument string for container
+            if let Some(reused) = reused_steps.iter().find(|x| x.step_number == step_number) {
+                recorder.update_step_status(step_id, reused.status).await?;
+                recorder.update_step_logs(step_id, reused.log_key.clone().unwrap_or_default()).await?;
+                if let Some(images) = &reused.image_digests {
+                    recorder.update_step_images(step_id, images.clone()).await?;
+                }
+                continue;
+            }
 
-            let mut corrected_args = Vec::new();
-            corrected_args.push(String::from("-c"));
-            let mut args_to_correct = Vec::new();
-            if let Some(secrets) = &secrets_generated {
-                args_to_correct.append(&mut secrets.to_source_commands());
+            if !step.should_run_for(run.changed_files.as_deref())? {
+                recorder.update_step_status(step_id, StepStatus::Skipped).await?;
+                continue;
             }
-            args_to_correct.append(&mut step.commands.clone());
-            let fixed_arg = correct_args(args_to_correct);
-            corrected_args.push(fixed_arg.expect("failed to build corrected args"));
-            
-            // create step cfg
 
-            let pipeline_step_config = PipelineJobConfig {
-                pipeline: pipeline_uuid.to_string(),
+            recorder.update_step_status(step_id, StepStatus::InProgress).await?;
+
+            // grab all secrets necessary for this step
+
+            let secrets_generated = build_step_secrets(step.clone(), secrets.clone()).await?;
+            
+            // create step cfg; the executor turns the commands into what its shell runs
+
+            let mut pipeline_step_config = PipelineJobConfig {
+                pipeline: run.pipeline_uuid,
+                workspace: run.workspace_uuid,
+                step_id,
+                step_number,
+                attempt: 1,
                 step: name.clone(),
                 container: step.image.clone(),
-                commands: corrected_args,
+                commands: step.commands.clone(),
                 pipeline_working_directory: pipeline_working_directory.clone(),
-                annotations: match secrets_generated.is_some() {
-                    true => Some(secrets_generated.unwrap()),
-                    false => None,
-                },
+                annotations: secrets_generated,
+                environment: run.parameters.clone(),
+                elevated: step.elevated,
+                image_build: step.build_image.clone(),
             };
 
-            // run the job on k8s
-
-            let data = crate::kube::build_pipeline_job(pipeline_step_config)?;
-            jobs.create(&PostParams::default(), &data.0).await?;
+            // run the step and wait until it is complete or failed, starting it over whenever the cluster takes it away
 
-            // begin streaming logs to redis
-            // TODO: job name is wrong, needs to be pipeline-UUID-container name.
-            let logs_stream_fut = logs_to_redis(state.redis(), data.1.clone(), data.2.clone(), name.clone());
+            let (running_step, log_names, outcome) = loop {
+                let mut running_step = executor.run_step(pipeline_step_config.clone()).await?;
+                executor.stream_logs(&mut running_step).await?;
 
+                // the step is gone if the server cancelled the pipeline while it ran
+                if recorder.is_cancelled().await? {
+                    return Ok(PipelineStatus::Cancelled);
+                }
 
-            // wait until the CI/CD job is complete or failed
-    
-            let pipeline_job_name = format!("pipeline-{pipeline_uuid}-{name}");
-            let job_done_fut = kube::runtime::wait::await_condition(jobs.clone(), &pipeline_job_name, conditions::Condition::or(conditions::is_job_completed(), crate::kube::utils::is_job_failed()));
+                let outcome = executor.exit_status(&mut running_step).await?;
+                let log_names = match &outcome {
+                    // the logs may have gone with the pod
+                    StepOutcome::Interrupted(_) => executor.archive_logs(&running_step).await.unwrap_or_else(|err| {
+                        println!("failed to archive logs of step {name}: {err}");
+                        Vec::new()
+                    }),
+                    _ => executor.archive_logs(&running_step).await?,
+                };
 
-            let logs_stream_handle = tokio::spawn(logs_stream_fut);
+                match outcome {
+                    StepOutcome::Interrupted(reason) if pipeline_step_config.attempt <= executor.infrastructure_retries() => {
+                        let attempt = StepAttempt { attempt: pipeline_step_config.attempt, reason, log_keys: log_names, finished_at: Utc::now() };
+                        recorder.add_interrupted_attempt(step_id, attempt).await?;
+                        executor.cleanup_step(running_step).await?;
+                        pipeline_step_config.attempt += 1;
+                    },
+                    outcome => break (running_step, log_names, outcome),
+                }
+            };
 
-            let res = tokio::join!(
-                logs_stream_handle,
-                job_done_fut
-            );
+            // check if step failed. if so, bail from pipeline with pipelinestatus::failed
 
-            res.0.expect("failed to join")?;
-            res.1?;
+            let step_status = match outcome {
+                StepOutcome::Succeeded => StepStatus::Success,
+                StepOutcome::Failed(reason) => {
+                    if let Some(reason) = reason {
+                        recorder.update_step_failure_reason(step_id, reason).await?;
+                    }
+                    StepStatus::Fail
+                },
+                StepOutcome::Interrupted(reason) => {
+                    recorder.update_step_failure_reason(step_id, format!("{reason} (gave up after attempt {})", pipeline_step_config.attempt)).await?;
+                    StepStatus::Fail
+                },
+            };
 
-            // upload pod logs to S3
-            let log_names = put_pod_logs_to_s3(data.1.clone(), Some(data.2), data.1, state.s3_bucket()).await?;
+            if step_status == StepStatus::Success {
+                let images = executor.pushed_images(&running_step).await?;
+                if !images.is_empty() {
+                    recorder.update_step_images(step_id, images).await?;
+                }
+            }
 
-            // check if job failed. if so, bail from pipeline with pipelinestatus::failed
+            recorder.update_step_status(step_id, step_status).await?;
+            recorder.update_step_logs(step_id, log_names).await?;
+            executor.cleanup_step(running_step).await?;
 
-            let job_with_status = jobs.get_status(&data.0.metadata.name.expect("failed to find job name")).await?;
-            if let Some(_pcond) = job_with_status.status.expect("failed to get job status").conditions.expect("failed to get job conditions").iter().find(|c| c.type_ == "Failed" && c.status == "True") {
-                // delete the job
-                api::step::db::update_step_status(state.postgres(), step_id, StepStatus::Fail).await?;
-                api::step::db::update_step_logs(state.postgres(), step_id, log_names.clone()).await?;
-                delete_job(&pipeline_job_name).await?;
-                
+            if step_status == StepStatus::Fail {
                 return Ok(PipelineStatus::Failed);
             }
-
-            // delete the job
-            api::step::db::update_step_status(state.postgres(), step_id, StepStatus::Success).await?;
-            api::step::db::update_step_logs(state.postgres(), step_id, log_names.clone()).await?;
-            delete_job(&pipeline_job_name).await?;
         }
 
         Ok(PipelineStatus::Complete)
 }
 
-fn correct_args(args_to_correct: Vec<String>) -> Option<String> {
+pub(crate) fn correct_args(args_to_correct: Vec<String>) -> Option<String> {
     let mut sb = String::new();
 
     for (idx, arg) in args_to_correct.iter().enumerate() {
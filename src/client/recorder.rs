@@ -0,0 +1,150 @@
+use futures::future::BoxFuture;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{pipeline::{PipelineStatus, PipelineStep}, server::api::{job::db::get_job, step::{self, model::{StepAttempt, StepStatus}}}};
+
+use super::PipelineExecError;
+
+// where execute_pipeline keeps track of the steps of a run
+pub trait StepRecorder: Send {
+    fn insert_step<'a>(&'a mut self, step_number: i32, step: &'a PipelineStep) -> BoxFuture<'a, Result<Uuid, PipelineExecError>>;
+
+    fn update_step_status(&mut self, step_id: Uuid, status: StepStatus) -> BoxFuture<'_, Result<(), PipelineExecError>>;
+
+    fn update_step_logs(&mut self, step_id: Uuid, log_keys: Vec<String>) -> BoxFuture<'_, Result<(), PipelineExecError>>;
+
+    fn update_step_failure_reason(&mut self, step_id: Uuid, reason: String) -> BoxFuture<'_, Result<(), PipelineExecError>>;
+
+    // images the step pushed, as name@digest
+    fn update_step_images(&mut self, step_id: Uuid, images: Vec<String>) -> BoxFuture<'_, Result<(), PipelineExecError>>;
+
+    // an attempt of the step that the cluster cut short and that is about to be retried
+    fn add_interrupted_attempt(&mut self, step_id: Uuid, attempt: StepAttempt) -> BoxFuture<'_, Result<(), PipelineExecError>>;
+
+    // whether the run was cancelled from outside since it started
+    fn is_cancelled(&mut self) -> BoxFuture<'_, Result<bool, PipelineExecError>>;
+}
+
+// records steps in the steps table, for runs the server started
+pub struct PostgresRecorder {
+    pool: PgPool,
+    pipeline_uuid: Uuid,
+}
+
+impl PostgresRecorder {
+    pub fn new(pool: PgPool, pipeline_uuid: Uuid) -> PostgresRecorder {
+        PostgresRecorder { pool, pipeline_uuid }
+    }
+}
+
+impl StepRecorder for PostgresRecorder {
+    fn insert_step<'a>(&'a mut self, step_number: i32, step: &'a PipelineStep) -> BoxFuture<'a, Result<Uuid, PipelineExecError>> {
+        Box::pin(async move {
+            Ok(step::db::insert_step(self.pool.clone(), self.pipeline_uuid, step_number, step).await?)
+        })
+    }
+
+    fn update_step_status(&mut self, step_id: Uuid, status: StepStatus) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            Ok(step::db::update_step_status(self.pool.clone(), step_id, status).await?)
+        })
+    }
+
+    fn update_step_logs(&mut self, step_id: Uuid, log_keys: Vec<String>) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            Ok(step::db::update_step_logs(self.pool.clone(), step_id, log_keys).await?)
+        })
+    }
+
+    fn update_step_failure_reason(&mut self, step_id: Uuid, reason: String) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            Ok(step::db::update_step_failure_reason(self.pool.clone(), step_id, reason).await?)
+        })
+    }
+
+    fn update_step_images(&mut self, step_id: Uuid, images: Vec<String>) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            Ok(step::db::update_step_image_digests(self.pool.clone(), step_id, images).await?)
+        })
+    }
+
+    fn add_interrupted_attempt(&mut self, step_id: Uuid, attempt: StepAttempt) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            Ok(step::db::add_interrupted_attempt(self.pool.clone(), step_id, attempt).await?)
+        })
+    }
+
+    fn is_cancelled(&mut self) -> BoxFuture<'_, Result<bool, PipelineExecError>> {
+        Box::pin(async move {
+            let job_info = get_job(self.pipeline_uuid, self.pool.clone()).await?;
+            // a job that timed out is torn down just like a cancelled one
+            Ok(matches!(job_info.status, PipelineStatus::Cancelled | PipelineStatus::TimedOut))
+        })
+    }
+}
+
+// prints step transitions, for local runs that have nowhere to record them
+#[derive(Default)]
+pub struct ConsoleRecorder {
+    step_names: Vec<(Uuid, String)>,
+}
+
+impl ConsoleRecorder {
+    fn step_name(&self, step_id: Uuid) -> &str {
+        self.step_names.iter().find(|x| x.0 == step_id).map(|x| x.1.as_str()).unwrap_or_default()
+    }
+}
+
+impl StepRecorder for ConsoleRecorder {
+    fn insert_step<'a>(&'a mut self, _step_number: i32, step: &'a PipelineStep) -> BoxFuture<'a, Result<Uuid, PipelineExecError>> {
+        Box::pin(async move {
+            let step_id = Uuid::new_v4();
+            self.step_names.push((step_id, step.name.clone()));
+            Ok(step_id)
+        })
+    }
+
+    fn update_step_status(&mut self, step_id: Uuid, status: StepStatus) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            println!("step {}: {status:?}", self.step_name(step_id));
+            Ok(())
+        })
+    }
+
+    fn update_step_logs(&mut self, step_id: Uuid, log_keys: Vec<String>) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            for log_key in log_keys {
+                println!("step {}: logs written to {log_key}", self.step_name(step_id));
+            }
+            Ok(())
+        })
+    }
+
+    fn update_step_failure_reason(&mut self, step_id: Uuid, reason: String) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            println!("step {}: {reason}", self.step_name(step_id));
+            Ok(())
+        })
+    }
+
+    fn update_step_images(&mut self, step_id: Uuid, images: Vec<String>) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            for image in images {
+                println!("step {}: pushed {image}", self.step_name(step_id));
+            }
+            Ok(())
+        })
+    }
+
+    fn add_interrupted_attempt(&mut self, step_id: Uuid, attempt: StepAttempt) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            println!("step {}: attempt {} was interrupted, retrying: {}", self.step_name(step_id), attempt.attempt, attempt.reason);
+            Ok(())
+        })
+    }
+
+    fn is_cancelled(&mut self) -> BoxFuture<'_, Result<bool, PipelineExecError>> {
+        Box::pin(async move { Ok(false) })
+    }
+}
@@ -1,4 +1,4 @@
-use super::correct_args;
+use super::{correct_args, parse_env_file};
 
 
 #[test]
@@ -16,4 +16,15 @@ fn test_correct_arguments() {
     let actual = correct_args(args_to_correct).expect("failed to correct args");
 
     assert_eq!(expected.to_string(), actual);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_parse_env_file() {
+    let contents = "# local secrets\nAPI_TOKEN=abc=123\n\nexport REGISTRY_PASSWORD=\"hunter2\"\nnot a variable\n";
+
+    let values = parse_env_file(contents);
+
+    assert_eq!(2, values.len());
+    assert_eq!(Some(&String::from("abc=123")), values.get("API_TOKEN"));
+    assert_eq!(Some(&String::from("hunter2")), values.get("REGISTRY_PASSWORD"));
+}
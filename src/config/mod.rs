@@ -18,6 +18,69 @@ pub struct Config {
     pub vault_server: Option<String>,
     // required on the server only
     pub git_server_url: Option<String>,
+    // which forge the git server speaks; defaults to gitea
+    pub git_forge: Option<GitForge>,
+    // server-side token used to report commit statuses back to the forge
+    pub git_server_token: Option<String>,
+    // shared secret sent by the forge with every webhook delivery; GitLab webhooks are refused without one
+    pub webhook_secret: Option<String>,
+    // how many jobs may run at once across the cluster; defaults to 5
+    pub max_concurrent_jobs: Option<u32>,
+    // how many jobs of a single repo may run at once unless the repo overrides it; defaults to 2
+    pub max_concurrent_jobs_per_repo: Option<u32>,
+    // how long the workspace of a failed job is kept around for resuming; defaults to 24
+    pub workspace_retention_hours: Option<u32>,
+    // longest a job may run for, whatever timeout its pipeline asks for; defaults to 360
+    pub max_job_duration_minutes: Option<u32>,
+    // run dispatch, reconciliation and schedules only on the replica holding the leader lease; defaults to false
+    pub leader_election: Option<bool>,
+    // reconcile PipelineRun resources into jobs; needs the CRD installed. defaults to false
+    pub pipeline_run_controller: Option<bool>,
+    // name of the pod this process runs in, from the downward API. the server uses it as its leader lease identity,
+    // clients of pod workspaces to add their steps to themselves
+    pub pod_name: Option<String>,
+    // namespace every job, pvc and lease lives in; defaults to constructum
+    pub kube_namespace: Option<String>,
+    // storage class of workspace PVCs unless the repo overrides it; defaults to nfs-ephemeral-client
+    pub workspace_storage_class: Option<String>,
+    // size of workspace PVCs unless the repo overrides it; defaults to 2Gi
+    pub workspace_size: Option<String>,
+    // ConfigMap the client job gets its environment from; defaults to constructum-cfg
+    pub client_config_map: Option<String>,
+    // service account of the client job; defaults to constructum-client-validate
+    pub client_service_account: Option<String>,
+    // service account of steps that use secrets; defaults to constructum-client-build
+    pub build_service_account: Option<String>,
+    // uid step containers run as; defaults to 1000
+    pub step_run_as_user: Option<i64>,
+    // gid step containers run as and the workspace is shared with; defaults to the uid
+    pub step_run_as_group: Option<i64>,
+    // capabilities dropped from step containers, comma separated; defaults to ALL
+    pub step_drop_capabilities: Option<Vec<String>>,
+    // seccomp profile of step pods: RuntimeDefault, Unconfined or localhost/<profile>; defaults to RuntimeDefault
+    pub step_seccomp_profile: Option<String>,
+    // mount the root filesystem of step containers read-only; defaults to false
+    pub step_read_only_root_filesystem: Option<bool>,
+    // repos (owner/name) whose pipelines may mark steps as elevated to run with the settings of their image, comma separated
+    pub elevated_step_repos: Option<Vec<String>>,
+    // kaniko image build_image steps run in; defaults to gcr.io/kaniko-project/executor:v1.9.2
+    pub image_builder: Option<String>,
+    // how often a step is started again after the cluster took it away, e.g. by evicting its pod; defaults to 2
+    pub step_infrastructure_retries: Option<u32>,
+    // how long a pod may wait for its PVC or a new node before its job or step counts as stuck; defaults to 300
+    pub unschedulable_grace_seconds: Option<u64>,
+    // entries kept in the redis stream of a live step log before the oldest are trimmed; defaults to 100000
+    pub log_stream_max_len: Option<usize>,
+    // how long a live step log stays in redis after its last line; defaults to 1800
+    pub log_ttl_seconds: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GitForge {
+    #[default]
+    Gitea,
+    Gitlab,
 }
 
 pub async fn build_database_clients(config: &Config) -> Result<(Pool<Postgres>, Bucket, redis::Client), ConstructumConfigError> {
@@ -0,0 +1,169 @@
+use std::path::{Path, PathBuf};
+
+use futures::future::BoxFuture;
+use k8s_openapi::api::{batch::v1::Job, core::v1::Pod};
+use kube::{Api, api::{ListParams, PostParams}};
+use s3::Bucket;
+
+use uuid::Uuid;
+
+use crate::{client::PipelineExecError, git, kube::{await_job, build_pipeline_job, delete_job, put_pod_logs_to_s3, utils::interruption_reason, JobWaitOutcome, KubeSettings, ResourceIdentity}, pipeline::PipelineJobConfig, redis::{logs_to_redis, LiveLogs}, server::api::{job::JobInfo, repo::RepoInfo}};
+
+use super::{read_pushed_images, shell_args, StepExecutor, StepOutcome};
+
+// runs every step as its own Job, all mounting the workspace PVC of the pipeline
+pub struct KubernetesExecutor {
+    jobs: Api<Job>,
+    pods: Api<Pod>,
+    settings: KubeSettings,
+    identity: ResourceIdentity,
+    redis: LiveLogs,
+    s3_bucket: Bucket,
+    workspace_root: PathBuf,
+    repo_url: String,
+    repo_name: String,
+    commit_id: String,
+}
+
+pub struct KubernetesStep {
+    job_name: String,
+    container_name: String,
+    step_id: Uuid,
+    builds_image: bool,
+    // whether the Job of the step was created at all
+    created: bool,
+    // why the step never got going, if it did not
+    stuck: Option<String>,
+}
+
+impl KubernetesExecutor {
+    pub async fn new(workspace_root: &Path, job_info: &JobInfo, repo_info: &RepoInfo, redis: LiveLogs, s3_bucket: Bucket, settings: KubeSettings) -> Result<KubernetesExecutor, PipelineExecError> {
+        let k8s_client = kube::Client::try_default().await?;
+
+        Ok(KubernetesExecutor {
+            jobs: Api::namespaced(k8s_client.clone(), &settings.namespace),
+            pods: Api::namespaced(k8s_client, &settings.namespace),
+            settings,
+            identity: ResourceIdentity::new(job_info, repo_info),
+            redis,
+            s3_bucket,
+            workspace_root: workspace_root.to_path_buf(),
+            repo_url: repo_info.repo_url.clone(),
+            repo_name: repo_info.repo_name.clone(),
+            commit_id: job_info.commit_id.clone(),
+        })
+    }
+}
+
+impl StepExecutor for KubernetesExecutor {
+    type Handle = KubernetesStep;
+
+    fn prepare_workspace(&mut self) -> BoxFuture<'_, Result<PathBuf, PipelineExecError>> {
+        Box::pin(async move {
+            let (_, working_directory) = git::pull_repository(&self.workspace_root, self.repo_url.clone(), self.repo_name.clone(), self.commit_id.clone()).await?;
+            Ok(working_directory)
+        })
+    }
+
+    fn run_step(&mut self, mut step: PipelineJobConfig) -> BoxFuture<'_, Result<KubernetesStep, PipelineExecError>> {
+        Box::pin(async move {
+            // vault injects the secrets of the step as files the script has to source
+            let source_commands = step.annotations.as_ref().map(|x| x.to_source_commands()).unwrap_or_default();
+            step.commands = shell_args(&step.commands, source_commands);
+
+            let step_id = step.step_id;
+            let builds_image = step.image_build.is_some();
+            if step.elevated && !self.settings.allows_elevated_steps(&self.identity.repo_name) {
+                let reason = format!("step {} is elevated, which {} is not allowed to run", step.step, self.identity.repo_name);
+                return Ok(KubernetesStep { job_name: String::new(), container_name: String::new(), step_id, builds_image, created: false, stuck: Some(reason) });
+            }
+
+            let (job, job_name, container_name) = build_pipeline_job(step, &self.identity, &self.settings)?;
+            self.jobs.create(&PostParams::default(), &job).await?;
+
+            Ok(KubernetesStep { job_name, container_name, step_id, builds_image, created: true, stuck: None })
+        })
+    }
+
+    fn stream_logs<'a>(&'a mut self, handle: &'a mut KubernetesStep) -> BoxFuture<'a, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            if !handle.created {
+                return Ok(());
+            }
+
+            let logs_stream_handle = tokio::spawn(logs_to_redis(self.redis.clone(), self.identity.job_uuid, handle.step_id.to_string(), handle.job_name.clone(), handle.container_name.clone(), self.settings.namespace.clone()));
+
+            // the job disappears if the server cancels the pipeline while it runs
+            match await_job(&handle.job_name, &self.settings).await? {
+                JobWaitOutcome::Stuck(reason) => {
+                    // there is nothing to stream from a container that never started
+                    logs_stream_handle.abort();
+                    handle.stuck = Some(reason);
+                },
+                JobWaitOutcome::Finished | JobWaitOutcome::Deleted => {
+                    logs_stream_handle.await.expect("failed to join")?;
+                },
+            }
+
+            Ok(())
+        })
+    }
+
+    fn archive_logs<'a>(&'a mut self, handle: &'a KubernetesStep) -> BoxFuture<'a, Result<Vec<String>, PipelineExecError>> {
+        Box::pin(async move {
+            if handle.stuck.is_some() {
+                return Ok(Vec::new());
+            }
+
+            Ok(put_pod_logs_to_s3(handle.job_name.clone(), Some(handle.container_name.clone()), handle.job_name.clone(), self.s3_bucket.clone(), &self.settings.namespace).await?)
+        })
+    }
+
+    fn exit_status<'a>(&'a mut self, handle: &'a mut KubernetesStep) -> BoxFuture<'a, Result<StepOutcome, PipelineExecError>> {
+        Box::pin(async move {
+            if let Some(reason) = &handle.stuck {
+                return Ok(StepOutcome::Failed(Some(reason.clone())));
+            }
+
+            let job_with_status = self.jobs.get_status(&handle.job_name).await?;
+            let failed = job_with_status.status
+                .and_then(|x| x.conditions)
+                .map(|x| x.iter().any(|c| c.type_ == "Failed" && c.status == "True"))
+                .unwrap_or(false);
+
+            if !failed {
+                return Ok(StepOutcome::Succeeded);
+            }
+
+            // the pods are still around until cleanup_step, and tell a script failure from an eviction
+            let pods = self.pods.list(&ListParams::default().labels(&format!("job-name={}", handle.job_name))).await?;
+            match interruption_reason(&pods.items) {
+                Some(reason) => Ok(StepOutcome::Interrupted(reason)),
+                None => Ok(StepOutcome::Failed(None)),
+            }
+        })
+    }
+
+    fn pushed_images<'a>(&'a mut self, handle: &'a KubernetesStep) -> BoxFuture<'a, Result<Vec<String>, PipelineExecError>> {
+        Box::pin(async move {
+            if !handle.builds_image {
+                return Ok(Vec::new());
+            }
+
+            read_pushed_images(&self.workspace_root, handle.step_id).await
+        })
+    }
+
+    fn cleanup_step(&mut self, handle: KubernetesStep) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            if handle.created {
+                delete_job(&handle.job_name, &self.settings.namespace).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn infrastructure_retries(&self) -> u32 {
+        self.settings.infrastructure_retries
+    }
+}
@@ -0,0 +1,181 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, process::{ExitStatus, Stdio}};
+
+use futures::future::BoxFuture;
+use tokio::{io::{AsyncBufReadExt, BufReader}, process::{Child, Command}};
+
+use crate::{client::PipelineExecError, pipeline::PipelineJobConfig};
+
+use super::{shell_args, StepExecutor, StepOutcome};
+
+// runs every step on this machine, in an existing checkout. steps either run as plain shell processes,
+// in which case their image is ignored and their tools have to be installed locally, or in their image through docker.
+pub struct LocalExecutor {
+    working_directory: PathBuf,
+    log_directory: PathBuf,
+    // secret values by the variable a step sees them in
+    secrets: HashMap<String, String>,
+    use_containers: bool,
+}
+
+pub struct LocalStep {
+    step_name: String,
+    child: Child,
+    log: String,
+    status: Option<ExitStatus>,
+}
+
+impl LocalExecutor {
+    pub fn new(working_directory: &Path, log_directory: &Path, secrets: HashMap<String, String>, use_containers: bool) -> LocalExecutor {
+        LocalExecutor {
+            working_directory: working_directory.to_path_buf(),
+            log_directory: log_directory.to_path_buf(),
+            secrets,
+            use_containers,
+        }
+    }
+
+    fn build_command(&self, step: &PipelineJobConfig, environment: &HashMap<String, String>) -> Result<Command, PipelineExecError> {
+        // images are only built, pushing them is left to runs in the cluster
+        if let Some(build) = &step.image_build {
+            if !self.use_containers {
+                return Err(PipelineExecError::UnsupportedStep(format!("step {} builds an image, which needs --docker", step.step)));
+            }
+
+            let working_directory = &step.pipeline_working_directory;
+            let mut command = Command::new("docker");
+            command.arg("build").arg("-f").arg(build.dockerfile_path(working_directory));
+            for tag in &build.tags {
+                command.args(["-t", tag.as_str()]);
+            }
+            for (name, value) in &build.build_args {
+                command.arg("--build-arg").arg(format!("{name}={value}"));
+            }
+            command.arg(build.context_path(working_directory));
+            return Ok(command);
+        }
+
+        let args = shell_args(&step.commands, Vec::new());
+
+        if !self.use_containers {
+            let mut command = Command::new("/bin/sh");
+            command.args(&args);
+            return Ok(command);
+        }
+
+        // mount the checkout at the same path so the working directory means the same inside and out
+        let working_directory = step.pipeline_working_directory.to_string_lossy();
+        let mount = format!("{working_directory}:{working_directory}");
+        let mut command = Command::new("docker");
+        command.args(["run", "--rm", "-v", mount.as_str(), "-w", working_directory.as_ref()]);
+        // values are passed through the environment of the docker cli rather than its arguments
+        for variable in environment.keys() {
+            command.args(["-e", variable.as_str()]);
+        }
+        command.arg(&step.container).arg("/bin/sh").args(&args);
+        Ok(command)
+    }
+}
+
+impl LocalStep {
+    fn record_line(&mut self, line: String) {
+        println!("[{}] {line}", self.step_name);
+        self.log.push_str(&line);
+        self.log.push('\n');
+    }
+}
+
+impl StepExecutor for LocalExecutor {
+    type Handle = LocalStep;
+
+    fn prepare_workspace(&mut self) -> BoxFuture<'_, Result<PathBuf, PipelineExecError>> {
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&self.log_directory).await?;
+            Ok(self.working_directory.clone())
+        })
+    }
+
+    fn run_step(&mut self, step: PipelineJobConfig) -> BoxFuture<'_, Result<LocalStep, PipelineExecError>> {
+        Box::pin(async move {
+            let mut environment = step.environment.clone();
+            if let Some(annotations) = &step.annotations {
+                for variable in annotations.variable_names() {
+                    // every secret was checked against the env file before the run started
+                    if let Some(value) = self.secrets.get(&variable) {
+                        environment.insert(variable, value.clone());
+                    }
+                }
+            }
+
+            let child = self.build_command(&step, &environment)?
+                .current_dir(&step.pipeline_working_directory)
+                .envs(&environment)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()?;
+
+            Ok(LocalStep { step_name: step.step, child, log: String::new(), status: None })
+        })
+    }
+
+    fn stream_logs<'a>(&'a mut self, handle: &'a mut LocalStep) -> BoxFuture<'a, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            let mut stdout = BufReader::new(handle.child.stdout.take().expect("failed to take stdout")).lines();
+            let mut stderr = BufReader::new(handle.child.stderr.take().expect("failed to take stderr")).lines();
+            let mut stdout_done = false;
+            let mut stderr_done = false;
+
+            while !(stdout_done && stderr_done) {
+                tokio::select! {
+                    line = stdout.next_line(), if !stdout_done => match line? {
+                        Some(line) => handle.record_line(line),
+                        None => stdout_done = true,
+                    },
+                    line = stderr.next_line(), if !stderr_done => match line? {
+                        Some(line) => handle.record_line(line),
+                        None => stderr_done = true,
+                    },
+                }
+            }
+
+            handle.status = Some(handle.child.wait().await?);
+            Ok(())
+        })
+    }
+
+    fn archive_logs<'a>(&'a mut self, handle: &'a LocalStep) -> BoxFuture<'a, Result<Vec<String>, PipelineExecError>> {
+        Box::pin(async move {
+            let log_file = self.log_directory.join(format!("{}.txt", handle.step_name));
+            tokio::fs::write(&log_file, handle.log.as_bytes()).await?;
+            Ok(vec![log_file.to_string_lossy().into_owned()])
+        })
+    }
+
+    fn exit_status<'a>(&'a mut self, handle: &'a mut LocalStep) -> BoxFuture<'a, Result<StepOutcome, PipelineExecError>> {
+        Box::pin(async move {
+            let status = match handle.status {
+                Some(status) => status,
+                None => handle.child.wait().await?,
+            };
+
+            match status.success() {
+                true => Ok(StepOutcome::Succeeded),
+                false => Ok(StepOutcome::Failed(None)),
+            }
+        })
+    }
+
+    fn pushed_images<'a>(&'a mut self, _handle: &'a LocalStep) -> BoxFuture<'a, Result<Vec<String>, PipelineExecError>> {
+        Box::pin(async move { Ok(Vec::new()) })
+    }
+
+    fn cleanup_step(&mut self, mut handle: LocalStep) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            if handle.status.is_none() {
+                handle.child.kill().await?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use std::path::{Path, PathBuf};
+
+use futures::future::BoxFuture;
+
+use uuid::Uuid;
+
+use crate::{client::{correct_args, PipelineExecError}, kube::image_digest_file, pipeline::PipelineJobConfig};
+
+mod kubernetes;
+mod local;
+mod pod;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::kubernetes::*;
+pub use self::local::*;
+pub use self::pod::*;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum StepOutcome {
+    Succeeded,
+    // with the reason when the step failed for something other than its own commands
+    Failed(Option<String>),
+    // the cluster took the step away before it finished, e.g. by evicting or preempting its pod
+    Interrupted(String),
+}
+
+// runs the steps of a pipeline somewhere. execute_pipeline drives one of these through
+// prepare_workspace once, then run_step, stream_logs, exit_status, archive_logs, pushed_images and cleanup_step per step.
+// methods return boxed futures so implementations can be picked at runtime without extra dependencies.
+pub trait StepExecutor: Send {
+    // whatever the executor needs to find a running step again
+    type Handle: Send;
+
+    // gets the repository in place for the steps and returns the directory they run in
+    fn prepare_workspace(&mut self) -> BoxFuture<'_, Result<PathBuf, PipelineExecError>>;
+
+    // starts the step without waiting for it to finish
+    fn run_step(&mut self, step: PipelineJobConfig) -> BoxFuture<'_, Result<Self::Handle, PipelineExecError>>;
+
+    // forwards the output of the step while it runs, returns once the step has stopped
+    fn stream_logs<'a>(&'a mut self, handle: &'a mut Self::Handle) -> BoxFuture<'a, Result<(), PipelineExecError>>;
+
+    // stores the full output of a stopped step and returns the keys it was stored under
+    fn archive_logs<'a>(&'a mut self, handle: &'a Self::Handle) -> BoxFuture<'a, Result<Vec<String>, PipelineExecError>>;
+
+    fn exit_status<'a>(&'a mut self, handle: &'a mut Self::Handle) -> BoxFuture<'a, Result<StepOutcome, PipelineExecError>>;
+
+    // images a succeeded step pushed, as name@digest
+    fn pushed_images<'a>(&'a mut self, handle: &'a Self::Handle) -> BoxFuture<'a, Result<Vec<String>, PipelineExecError>>;
+
+    // removes whatever the step left behind, apart from the workspace
+    fn cleanup_step(&mut self, handle: Self::Handle) -> BoxFuture<'_, Result<(), PipelineExecError>>;
+
+    // how often a step that ended up Interrupted is started again before it counts as failed
+    fn infrastructure_retries(&self) -> u32 {
+        0
+    }
+}
+
+// turns the commands of a step into the arguments of `/bin/sh`, running the given setup commands first
+fn shell_args(commands: &[String], mut setup_commands: Vec<String>) -> Vec<String> {
+    setup_commands.extend_from_slice(commands);
+    vec![String::from("-c"), correct_args(setup_commands).expect("failed to build corrected args")]
+}
+
+// kaniko lists what it pushed in a file in the workspace, which the client mounts as well
+async fn read_pushed_images(workspace_root: &Path, step_id: Uuid) -> Result<Vec<String>, PipelineExecError> {
+    let digest_file = image_digest_file(workspace_root, step_id);
+    let contents = tokio::fs::read_to_string(&digest_file).await?;
+    tokio::fs::remove_file(&digest_file).await?;
+    Ok(contents.lines().map(str::trim).filter(|x| !x.is_empty()).map(String::from).collect())
+}
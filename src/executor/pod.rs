@@ -0,0 +1,160 @@
+use std::path::{Path, PathBuf};
+
+use futures::future::BoxFuture;
+use k8s_openapi::api::core::v1::Pod;
+use kube::{Api, api::{Patch, PatchParams}};
+use s3::Bucket;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{client::PipelineExecError, git, kube::{await_ephemeral_container, build_step_ephemeral_container, client_job_name, put_pod_logs_to_s3, step_container_name, ContainerStage, ContainerWaitOutcome, KubeSettings, ResourceIdentity}, pipeline::PipelineJobConfig, redis::{logs_to_redis, LiveLogs}, server::api::{job::JobInfo, repo::RepoInfo}};
+
+use super::{read_pushed_images, shell_args, StepExecutor, StepOutcome};
+
+// runs every step as an ephemeral container of the client pod, all sharing the emptyDir the client cloned into.
+// the client service account needs to be allowed to patch pods/ephemeralcontainers.
+// steps that would run as the policy's uid run as the client user instead, since it owns the tree it cloned.
+pub struct PodExecutor {
+    pods: Api<Pod>,
+    pod_name: String,
+    settings: KubeSettings,
+    identity: ResourceIdentity,
+    redis: LiveLogs,
+    s3_bucket: Bucket,
+    workspace_root: PathBuf,
+    repo_url: String,
+    repo_name: String,
+    commit_id: String,
+}
+
+pub struct PodStep {
+    container_name: String,
+    step_id: Uuid,
+    builds_image: bool,
+    // whether the container of the step was added to the pod at all
+    created: bool,
+    // why the step never got going, if it did not
+    stuck: Option<String>,
+    exit_code: Option<i32>,
+}
+
+impl PodExecutor {
+    pub async fn new(workspace_root: &Path, pod_name: String, job_info: &JobInfo, repo_info: &RepoInfo, redis: LiveLogs, s3_bucket: Bucket, settings: KubeSettings) -> Result<PodExecutor, PipelineExecError> {
+        let k8s_client = kube::Client::try_default().await?;
+
+        Ok(PodExecutor {
+            pods: Api::namespaced(k8s_client, &settings.namespace),
+            pod_name,
+            settings,
+            identity: ResourceIdentity::new(job_info, repo_info),
+            redis,
+            s3_bucket,
+            workspace_root: workspace_root.to_path_buf(),
+            repo_url: repo_info.repo_url.clone(),
+            repo_name: repo_info.repo_name.clone(),
+            commit_id: job_info.commit_id.clone(),
+        })
+    }
+
+    fn not_started(step: &PipelineJobConfig, reason: String) -> PodStep {
+        PodStep { container_name: String::new(), step_id: step.step_id, builds_image: false, created: false, stuck: Some(reason), exit_code: None }
+    }
+}
+
+impl StepExecutor for PodExecutor {
+    type Handle = PodStep;
+
+    fn prepare_workspace(&mut self) -> BoxFuture<'_, Result<PathBuf, PipelineExecError>> {
+        Box::pin(async move {
+            let (_, working_directory) = git::pull_repository(&self.workspace_root, self.repo_url.clone(), self.repo_name.clone(), self.commit_id.clone()).await?;
+            Ok(working_directory)
+        })
+    }
+
+    fn run_step(&mut self, mut step: PipelineJobConfig) -> BoxFuture<'_, Result<PodStep, PipelineExecError>> {
+        Box::pin(async move {
+            if step.elevated && !self.settings.allows_elevated_steps(&self.identity.repo_name) {
+                let reason = format!("step {} is elevated, which {} is not allowed to run", step.step, self.identity.repo_name);
+                return Ok(Self::not_started(&step, reason));
+            }
+            // the vault injector only renders secrets into pods as they are created
+            if step.annotations.is_some() {
+                let reason = format!("step {} uses secrets, which steps of a pod workspace cannot", step.step);
+                return Ok(Self::not_started(&step, reason));
+            }
+
+            step.commands = shell_args(&step.commands, Vec::new());
+
+            let container = build_step_ephemeral_container(&step, &self.settings)?;
+            self.pods.patch_subresource("ephemeralcontainers", &self.pod_name, &PatchParams::default(), &Patch::Strategic(json!({
+                "spec": { "ephemeralContainers": [container] }
+            }))).await?;
+
+            Ok(PodStep { container_name: step_container_name(step.step_number), step_id: step.step_id, builds_image: step.image_build.is_some(), created: true, stuck: None, exit_code: None })
+        })
+    }
+
+    fn stream_logs<'a>(&'a mut self, handle: &'a mut PodStep) -> BoxFuture<'a, Result<(), PipelineExecError>> {
+        Box::pin(async move {
+            if !handle.created {
+                return Ok(());
+            }
+
+            // there is nothing to stream until the container runs
+            match await_ephemeral_container(&self.pod_name, &handle.container_name, ContainerStage::Started, &self.settings.namespace).await? {
+                ContainerWaitOutcome::Stuck(reason) => {
+                    handle.stuck = Some(reason);
+                    return Ok(());
+                },
+                ContainerWaitOutcome::PodDeleted => return Ok(()),
+                ContainerWaitOutcome::Running | ContainerWaitOutcome::Terminated(_) => {},
+            }
+
+            // the client pod carries the job-name label of the client job, which is how the logs find it
+            logs_to_redis(self.redis.clone(), self.identity.job_uuid, handle.step_id.to_string(), client_job_name(self.identity.job_uuid), handle.container_name.clone(), self.settings.namespace.clone()).await?;
+
+            if let ContainerWaitOutcome::Terminated(exit_code) = await_ephemeral_container(&self.pod_name, &handle.container_name, ContainerStage::Terminated, &self.settings.namespace).await? {
+                handle.exit_code = Some(exit_code);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn archive_logs<'a>(&'a mut self, handle: &'a PodStep) -> BoxFuture<'a, Result<Vec<String>, PipelineExecError>> {
+        Box::pin(async move {
+            if handle.stuck.is_some() {
+                return Ok(Vec::new());
+            }
+
+            // every step shares the pod, so the container name keeps their log files apart
+            Ok(put_pod_logs_to_s3(client_job_name(self.identity.job_uuid), Some(handle.container_name.clone()), handle.container_name.clone(), self.s3_bucket.clone(), &self.settings.namespace).await?)
+        })
+    }
+
+    fn exit_status<'a>(&'a mut self, handle: &'a mut PodStep) -> BoxFuture<'a, Result<StepOutcome, PipelineExecError>> {
+        Box::pin(async move {
+            match (&handle.stuck, handle.exit_code) {
+                (Some(reason), _) => Ok(StepOutcome::Failed(Some(reason.clone()))),
+                (None, Some(0)) => Ok(StepOutcome::Succeeded),
+                (None, Some(_)) => Ok(StepOutcome::Failed(None)),
+                (None, None) => Ok(StepOutcome::Failed(Some(format!("container {} disappeared before it finished", handle.container_name)))),
+            }
+        })
+    }
+
+    fn pushed_images<'a>(&'a mut self, handle: &'a PodStep) -> BoxFuture<'a, Result<Vec<String>, PipelineExecError>> {
+        Box::pin(async move {
+            if !handle.builds_image {
+                return Ok(Vec::new());
+            }
+
+            read_pushed_images(&self.workspace_root, handle.step_id).await
+        })
+    }
+
+    // ephemeral containers cannot be removed, they stay in the pod until it is deleted with the client job
+    fn cleanup_step(&mut self, _handle: PodStep) -> BoxFuture<'_, Result<(), PipelineExecError>> {
+        Box::pin(async move { Ok(()) })
+    }
+}
@@ -0,0 +1,51 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use uuid::Uuid;
+
+use crate::pipeline::PipelineJobConfig;
+
+use super::{LocalExecutor, StepExecutor, StepOutcome};
+
+fn step_config(name: &str, commands: &[&str], working_directory: PathBuf) -> PipelineJobConfig {
+    PipelineJobConfig {
+        pipeline: Uuid::new_v4(),
+        workspace: Uuid::new_v4(),
+        step_id: Uuid::new_v4(),
+        step_number: 0,
+        attempt: 1,
+        step: String::from(name),
+        container: String::from("alpine"),
+        commands: commands.iter().map(|x| String::from(*x)).collect(),
+        pipeline_working_directory: working_directory,
+        annotations: None,
+        environment: HashMap::from([(String::from("GREETING"), String::from("hello"))]),
+        elevated: false,
+        image_build: None,
+    }
+}
+
+#[tokio::test]
+async fn test_local_executor_runs_steps() {
+    let root = std::env::temp_dir().join(format!("constructum-{}", Uuid::new_v4()));
+    tokio::fs::create_dir_all(&root).await.expect("failed to create working directory");
+    let mut executor = LocalExecutor::new(&root, &root.join("logs"), HashMap::new(), false);
+
+    let working_directory = executor.prepare_workspace().await.expect("failed to prepare workspace");
+
+    let mut passing = executor.run_step(step_config("passing", &["echo $GREETING", "echo oops >&2"], working_directory.clone())).await.expect("failed to start step");
+    executor.stream_logs(&mut passing).await.expect("failed to stream logs");
+    let log_files = executor.archive_logs(&passing).await.expect("failed to archive logs");
+    assert_eq!(StepOutcome::Succeeded, executor.exit_status(&mut passing).await.expect("failed to get exit status"));
+    executor.cleanup_step(passing).await.expect("failed to clean up");
+
+    let log = tokio::fs::read_to_string(&log_files[0]).await.expect("failed to read log");
+    assert!(log.contains("hello"));
+    assert!(log.contains("oops"));
+
+    let mut failing = executor.run_step(step_config("failing", &["exit 3"], working_directory)).await.expect("failed to start step");
+    executor.stream_logs(&mut failing).await.expect("failed to stream logs");
+    assert_eq!(StepOutcome::Failed(None), executor.exit_status(&mut failing).await.expect("failed to get exit status"));
+    executor.cleanup_step(failing).await.expect("failed to clean up");
+
+    tokio::fs::remove_dir_all(&root).await.expect("failed to remove working directory");
+}
@@ -2,11 +2,15 @@ use std::{fmt::Display, error::Error, path::{Path, PathBuf}};
 
 use tokio::fs::File;
 
+#[cfg(test)]
+mod tests;
+
 
 #[derive(Debug)]
 pub enum GitError {
     IOError(std::io::Error),
     NoConstructumYml,
+    UnknownRevision(String),
 }
 
 impl Display for GitError {
@@ -14,6 +18,7 @@ impl Display for GitError {
         match self {
             GitError::IOError(io) => write!(f, "Git Error: IO Error: {io}"),
             GitError::NoConstructumYml => write!(f, "Git Error: No .constructum.yml file found"),
+            GitError::UnknownRevision(rev) => write!(f, "Git Error: Could not resolve revision {rev}"),
         }
     }
 }
@@ -53,6 +58,71 @@ pub async fn pull_repository(root: &Path, repo_location: String, repo_name: Stri
     read_pipeline_file(root, repo_name).await.map(|x| (x, pipeline_file_location))
 }
  
+// resolves a branch, tag or (partial) commit to the full commit hash it points at
+pub async fn resolve_revision(root: &Path, repo_location: String, repo_name: String, revision: String) -> Result<String, GitError> {
+    check_revision(&revision)?;
+
+    let pipeline_file_location = create_repo_directory(root, repo_name).await?;
+    fetch_repo(&pipeline_file_location, &repo_location).await?;
+
+    let mut git_rev_parse = tokio::process::Command::new("git");
+    git_rev_parse.args(["rev-parse", "--verify", "--quiet", "--end-of-options", &format!("{revision}^{{commit}}")]);
+    git_rev_parse.current_dir(&pipeline_file_location);
+    let output = git_rev_parse.output().await?;
+
+    if !output.status.success() {
+        return Err(GitError::UnknownRevision(revision));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+// revisions come from api callers, so one must never be taken for an option of git
+fn check_revision(revision: &str) -> Result<(), GitError> {
+    if revision.is_empty() || revision.starts_with('-') {
+        return Err(GitError::UnknownRevision(revision.to_owned()));
+    }
+
+    Ok(())
+}
+
+// asks the remote which branch HEAD points at, since a plain fetch does not record origin/HEAD
+pub async fn default_branch(root: &Path, repo_location: String, repo_name: String) -> Result<String, GitError> {
+    let pipeline_file_location = create_repo_directory(root, repo_name).await?;
+    fetch_repo(&pipeline_file_location, &repo_location).await?;
+
+    let mut git_ls_remote = tokio::process::Command::new("git");
+    git_ls_remote.args(["ls-remote", "--symref", "origin", "HEAD"]);
+    git_ls_remote.current_dir(&pipeline_file_location);
+    let output = git_ls_remote.output().await?;
+
+    // expected output is `ref: refs/heads/<branch>\tHEAD` followed by the commit line
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|x| x.strip_prefix("ref: refs/heads/"))
+        .and_then(|x| x.split('\t').next())
+        .map(String::from)
+        .ok_or_else(|| GitError::UnknownRevision(String::from("HEAD")))
+}
+
+// lists the files touched between two commits of an already fetched repository.
+// returns None if git cannot diff them, e.g. when `before` was force-pushed away or is beyond the fetch depth.
+pub async fn changed_files(root: &Path, repo_name: String, before: String, after: String) -> Result<Option<Vec<String>>, GitError> {
+    let mut repo_location = root.to_path_buf();
+    repo_location.push(repo_name);
+
+    let mut git_diff = tokio::process::Command::new("git");
+    git_diff.args(["diff", "--name-only", &before, &after]);
+    git_diff.current_dir(&repo_location);
+    let output = git_diff.output().await?;
+
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    Ok(Some(String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect()))
+}
+
 async fn fetch_repo(pipeline_file_location: &Path, repo_location: &str) -> Result<(), GitError> {
     let mut git_init_repo = tokio::process::Command::new("git");
     git_init_repo.arg("init");
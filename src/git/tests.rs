@@ -0,0 +1,12 @@
+use super::check_revision;
+
+#[test]
+fn test_check_revision_rejects_options() {
+    assert!(check_revision("--output=/tmp/pwned").is_err());
+    assert!(check_revision("-h").is_err());
+    assert!(check_revision("").is_err());
+
+    assert!(check_revision("origin/main").is_ok());
+    assert!(check_revision("refs/tags/v1.0.0").is_ok());
+    assert!(check_revision("3f2a9c1").is_ok());
+}
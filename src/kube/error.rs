@@ -5,6 +5,9 @@ use std::{fmt::Display, error::Error};
 pub enum ConstructumKubeError {
     Kube(kube::Error),
     KubeRuntimeWait(kube::runtime::wait::Error),
+    KubeWatch(kube::runtime::watcher::Error),
+    // a watch stream that closed, naming what was watched
+    WatchEnded(String),
 }
 
 impl Display for ConstructumKubeError {
@@ -12,6 +15,8 @@ impl Display for ConstructumKubeError {
         match self {
             ConstructumKubeError::Kube(kub) => write!(f, "Kubernetes Error: {kub}"),
             ConstructumKubeError::KubeRuntimeWait(wait) => write!(f, "Kubernetes Wait Error: {wait}"),
+            ConstructumKubeError::KubeWatch(watch) => write!(f, "Kubernetes Watch Error: {watch}"),
+            ConstructumKubeError::WatchEnded(watched) => write!(f, "Kubernetes Watch Error: watch of {watched} ended"),
         }
     }
 }
@@ -28,4 +33,10 @@ impl From<kube::runtime::wait::Error> for ConstructumKubeError {
     fn from(value: kube::runtime::wait::Error) -> Self {
         Self::KubeRuntimeWait(value)
     }
-}
\ No newline at end of file
+}
+
+impl From<kube::runtime::watcher::Error> for ConstructumKubeError {
+    fn from(value: kube::runtime::watcher::Error) -> Self {
+        Self::KubeWatch(value)
+    }
+}
@@ -0,0 +1,66 @@
+use chrono::{Duration, Utc};
+use k8s_openapi::{api::coordination::v1::{Lease, LeaseSpec}, apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta}};
+use kube::{Api, api::PostParams};
+
+use super::error::ConstructumKubeError;
+
+// takes or renews the lease for `identity`. returns false if another holder's lease is still valid
+// or another replica updated the lease between our read and write.
+pub async fn try_acquire_lease(lease_name: &str, identity: &str, lease_duration_seconds: i32, namespace: &str) -> Result<bool, ConstructumKubeError> {
+    let k8s_client = kube::Client::try_default().await?;
+    let leases: Api<Lease> = Api::namespaced(k8s_client, namespace);
+    let now = Utc::now();
+
+    let mut lease = match leases.get_opt(lease_name).await? {
+        Some(lease) => lease,
+        None => {
+            let lease = Lease {
+                metadata: ObjectMeta {
+                    name: Some(lease_name.to_owned()),
+                    namespace: Some(namespace.to_owned()),
+                    ..Default::default()
+                },
+                spec: Some(LeaseSpec {
+                    holder_identity: Some(identity.to_owned()),
+                    lease_duration_seconds: Some(lease_duration_seconds),
+                    acquire_time: Some(MicroTime(now)),
+                    renew_time: Some(MicroTime(now)),
+                    lease_transitions: Some(0),
+                }),
+            };
+
+            return match leases.create(&PostParams::default(), &lease).await {
+                Ok(_) => Ok(true),
+                // another replica created it first
+                Err(kube::Error::Api(err)) if err.code == 409 => Ok(false),
+                Err(err) => Err(err.into()),
+            };
+        }
+    };
+
+    let spec = lease.spec.get_or_insert_with(LeaseSpec::default);
+    let held_by_us = spec.holder_identity.as_deref() == Some(identity);
+    let expired = match &spec.renew_time {
+        Some(renew_time) => renew_time.0 + Duration::seconds(i64::from(spec.lease_duration_seconds.unwrap_or(lease_duration_seconds))) < now,
+        None => true,
+    };
+
+    if !held_by_us && !expired {
+        return Ok(false);
+    }
+
+    if !held_by_us {
+        spec.holder_identity = Some(identity.to_owned());
+        spec.acquire_time = Some(MicroTime(now));
+        spec.lease_transitions = Some(spec.lease_transitions.unwrap_or(0) + 1);
+    }
+    spec.renew_time = Some(MicroTime(now));
+    spec.lease_duration_seconds = Some(lease_duration_seconds);
+
+    // the resourceVersion we read makes this fail if the lease changed in the meantime
+    match leases.replace(lease_name, &PostParams::default(), &lease).await {
+        Ok(_) => Ok(true),
+        Err(kube::Error::Api(err)) if err.code == 409 => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
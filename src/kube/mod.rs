@@ -1,7 +1,7 @@
-use std::{path::PathBuf, pin::Pin};
+use std::{path::{Path, PathBuf}, pin::Pin};
 
 use futures::Stream;
-use k8s_openapi::api::{batch::v1::Job, core::v1::{PersistentVolumeClaim, Pod}};
+use k8s_openapi::api::{batch::v1::Job, core::v1::{EphemeralContainer, PersistentVolumeClaim, Pod}};
 use kube::{Api, api::{LogParams, ListParams, DeleteParams}, runtime::wait::{conditions, await_condition}};
 use s3::Bucket;
 use serde::{Serialize, Deserialize};
@@ -12,75 +12,128 @@ use bytes::Bytes;
 
 pub mod utils;
 pub mod error;
+mod lease;
+mod monitor;
+mod names;
+mod pipeline_run;
 mod secret;
+mod settings;
+
+#[cfg(test)]
+mod tests;
 
 use self::error::ConstructumKubeError;
+pub use self::lease::*;
+pub use self::monitor::*;
+pub use self::names::*;
+pub use self::pipeline_run::*;
 pub use self::secret::*;
+pub use self::settings::*;
+
+use crate::{pipeline::{ImageBuildConfig, PipelineJobConfig}, client::PipelineExecError};
+
+pub const CLIENT_CONTAINER_NAME: &str = "client";
+pub const STEP_CONTAINER_NAME: &str = "step";
+// where every job mounts its workspace
+pub const WORKSPACE_MOUNT_PATH: &str = "/data";
+const WORKSPACE_VOLUME_NAME: &str = "workspace";
+// the constructum user of the client image, which owns the tree it clones into a pod workspace
+pub const CLIENT_RUN_AS_USER: i64 = 10001;
+pub const CLIENT_RUN_AS_GROUP: i64 = 10001;
 
-use crate::{pipeline::PipelineJobConfig, client::PipelineExecError};
+pub fn build_client_pvc(identity: &ResourceIdentity, namespace: &str, storage_class: &str, workspace_size: &str) -> Result<PersistentVolumeClaim, serde_json::Error> {
+    let mut annotations = identity.annotations();
+    annotations.insert(String::from("volume.beta.kubernetes.io/storage-class"), String::from(storage_class));
 
-pub fn build_client_pvc(pipeline_uuid: Uuid) -> Result<PersistentVolumeClaim, serde_json::Error> {
     serde_json::from_value(serde_json::json!({
         "apiVersion": "v1",
         "kind": "PersistentVolumeClaim",
         "metadata": {
-            "name": format!("pipeline-{pipeline_uuid}-pvc"),
-            "namespace": "constructum",
-            "annotations": {
-                "volume.beta.kubernetes.io/storage-class": "nfs-ephemeral-client"
-            },
-            "labels": {
-                "constructum-pipeline": format!("{pipeline_uuid}")
-            },
+            "name": workspace_pvc_name(identity.job_uuid),
+            "namespace": namespace,
+            "annotations": annotations,
+            "labels": identity.labels(COMPONENT_WORKSPACE),
         },
         "spec": {
             "accessModes": [ "ReadWriteMany" ],
             "resources": {
                 "requests": {
-                    "storage": "2Gi"
+                    "storage": workspace_size
                 }
             },
-            "storageClassName": "nfs-ephemeral-client"
+            "storageClassName": storage_class
         }
     }))
 }
 
-pub fn build_client_job(pipeline_uuid: Uuid, pipeline_client_name: String, container_name: String, service_account_name: Option<String>) -> Result<Job, serde_json::Error> {
+// without a workspace PVC the pipeline runs in an emptyDir of the client pod, with its steps as ephemeral containers
+pub fn build_client_job(identity: &ResourceIdentity, workspace_uuid: Option<Uuid>, container_name: String, settings: &KubeSettings) -> Result<Job, serde_json::Error> {
+    let labels = identity.labels(COMPONENT_CLIENT);
+    let annotations = identity.annotations();
+    let (workspace_volume, security_context) = match workspace_uuid {
+        Some(workspace_uuid) => (serde_json::json!({
+            "name": WORKSPACE_VOLUME_NAME,
+            "persistentVolumeClaim": {
+                "claimName": workspace_pvc_name(workspace_uuid)
+            }
+        }), serde_json::Value::Null),
+        // pinned so the steps can run as the owner of the workspace, see build_step_ephemeral_container
+        None => (serde_json::json!({
+            "name": WORKSPACE_VOLUME_NAME,
+            "emptyDir": {}
+        }), serde_json::json!({
+            "runAsUser": CLIENT_RUN_AS_USER,
+            "runAsGroup": CLIENT_RUN_AS_GROUP,
+        })),
+    };
+
     serde_json::from_value(serde_json::json!({
         "apiVersion": "batch/v1",
         "kind": "Job",
         "metadata": {
-            "name": pipeline_client_name,
-            "namespace": "constructum",
+            "name": client_job_name(identity.job_uuid),
+            "namespace": settings.namespace,
+            "labels": labels,
+            "annotations": annotations,
         },
         "spec": {
             "backoffLimit": 0,
             "template": {
                 "metadata": {
-                    "name": format!("{pipeline_client_name}-pod"),
+                    "labels": labels,
+                    "annotations": annotations,
                 },
                 "spec": {
-                    "serviceAccountName": service_account_name,
+                    "serviceAccountName": settings.client_service_account,
+                    "securityContext": security_context,
                     "containers": [{
-                        "name": format!("{pipeline_client_name}-container"),
+                        "name": CLIENT_CONTAINER_NAME,
                         "image": container_name,
                         "envFrom": [ 
                             {
                                 "configMapRef": {
-                                    "name": "constructum-cfg"
+                                    "name": settings.config_map
                                 }
                             }
                         ],
                         "env": [
                             {
                                 "name": "CONSTRUCTUM_PIPELINE_UUID",
-                                "value": pipeline_uuid.to_string(),
+                                "value": identity.job_uuid.to_string(),
+                            },
+                            {
+                                "name": "CONSTRUCTUM_POD_NAME",
+                                "valueFrom": {
+                                    "fieldRef": {
+                                        "fieldPath": "metadata.name"
+                                    }
+                                }
                             }
                         ],
                         "volumeMounts": [
                             {
-                                "mountPath": "/data",
-                                "name": "data-pvc"
+                                "mountPath": WORKSPACE_MOUNT_PATH,
+                                "name": WORKSPACE_VOLUME_NAME
                             },
                             {
                                 "mountPath": "/var/run/secrets/tokens",
@@ -89,12 +142,7 @@ pub fn build_client_job(pipeline_uuid: Uuid, pipeline_client_name: String, conta
                         ]
                     }],
                     "volumes": [
-                        {
-                            "name": "data-pvc",
-                            "persistentVolumeClaim": {
-                                "claimName": format!("pipeline-{pipeline_uuid}-pvc")
-                            }
-                        },
+                        workspace_volume,
                         {
                             "name": "vault-token",
                             "projected": {
@@ -115,51 +163,61 @@ pub fn build_client_job(pipeline_uuid: Uuid, pipeline_client_name: String, conta
     }))
 }
 
-pub fn build_pipeline_job(job_cfg: PipelineJobConfig) -> Result<(Job, String, String), serde_json::Error> {
-    // TODO: this might exceed the k8s resource limit. re-encode the uuid.
-    let pipeline_job_name = format!("pipeline-{}-{}", job_cfg.pipeline, job_cfg.step);
+pub fn build_pipeline_job(job_cfg: PipelineJobConfig, identity: &ResourceIdentity, settings: &KubeSettings) -> Result<(Job, String, String), serde_json::Error> {
+    let pipeline_job_name = step_job_name(identity.job_uuid, job_cfg.step_number, job_cfg.attempt);
+    let labels = identity.step_labels(job_cfg.step_id);
+    let annotations = identity.step_annotations(&job_cfg.step);
+
     let sa_name = match job_cfg.annotations.is_some() {
-        true => Some("constructum-client-build"),
-        false => None,
-    };
-    let secret_env_from = match job_cfg.annotations.is_some() {
-        true => Some(job_cfg.annotations.unwrap().to_serde_values()),
+        true => Some(settings.build_service_account.clone()),
         false => None,
     };
+    let process = step_process(&job_cfg, settings);
 
-    let container_name = format!("{}-container", job_cfg.step);
+    // the pod carries the vault injector annotations on top of the identity of the step
+    let mut pod_annotations: serde_json::Map<String, serde_json::Value> = annotations.iter().map(|(k, v)| (k.clone(), serde_json::json!(v))).collect();
+    if let Some(serde_json::Value::Object(secret_annotations)) = job_cfg.annotations.map(|x| x.to_serde_values()) {
+        pod_annotations.extend(secret_annotations);
+    }
+
+    let container_name = String::from(STEP_CONTAINER_NAME);
 
     Ok((serde_json::from_value(serde_json::json!({
         "apiVersion": "batch/v1",
         "kind": "Job",
         "metadata": {
             "name": pipeline_job_name,
-            "namespace": "constructum",
+            "namespace": settings.namespace,
+            "labels": labels,
+            "annotations": annotations,
         },
         "spec": {
             "backoffLimit": 0,
             "template": {
                 "metadata": {
-                    "name": format!("{pipeline_job_name}-pod"),
-                    "annotations": secret_env_from,
+                    "labels": labels,
+                    "annotations": pod_annotations,
                 },
                 "spec": {
                     "serviceAccountName": sa_name,
+                    "securityContext": process.pod_security_context,
                     "containers": [{
                         "name": container_name,
-                        "image": job_cfg.container,
+                        "image": process.image,
+                        "securityContext": process.container_security_context,
+                        "env": process.env,
                         "volumeMounts": [{
-                            "mountPath": "/data",
-                            "name": "data-pvc"
+                            "mountPath": WORKSPACE_MOUNT_PATH,
+                            "name": WORKSPACE_VOLUME_NAME
                         }],
-                        "command": [ "/bin/sh" ],
-                        "args": job_cfg.commands,
-                        "workingDir": format!("{}", job_cfg.pipeline_working_directory.display())
+                        "command": process.command,
+                        "args": process.args,
+                        "workingDir": process.working_dir
                     }],
                     "volumes": [{
-                        "name": "data-pvc",
+                        "name": WORKSPACE_VOLUME_NAME,
                         "persistentVolumeClaim": {
-                            "claimName": format!("pipeline-{}-pvc", job_cfg.pipeline)
+                            "claimName": workspace_pvc_name(job_cfg.workspace)
                         }
                     }],
                     "restartPolicy": "Never",
@@ -169,9 +227,107 @@ pub fn build_pipeline_job(job_cfg: PipelineJobConfig) -> Result<(Job, String, St
     }))?, pipeline_job_name, container_name))
 }
 
-pub async fn put_pod_logs_to_s3(job_name: String, container_name: Option<String>, file_name: String, s3_bucket: Bucket) -> Result<Vec<String>, ConstructumKubeError> {
+// the step as an ephemeral container of the client pod, for pipelines whose workspace is an emptyDir of that pod
+pub fn build_step_ephemeral_container(job_cfg: &PipelineJobConfig, settings: &KubeSettings) -> Result<EphemeralContainer, serde_json::Error> {
+    let process = step_process(job_cfg, settings);
+
+    // an ephemeral container cannot change the settings of the pod it joins, so the pod-wide part of the policy moves onto the container.
+    // fsGroup has no container equivalent, so steps that would drop root run as the client instead, which cloned the workspace
+    let mut security_context = match process.container_security_context {
+        serde_json::Value::Object(context) => context,
+        _ => serde_json::Map::new(),
+    };
+    if let serde_json::Value::Object(pod_context) = process.pod_security_context {
+        security_context.extend(pod_context.into_iter().filter(|(k, _)| k != "fsGroup"));
+    }
+    if security_context.contains_key("runAsUser") {
+        security_context.insert(String::from("runAsUser"), serde_json::json!(CLIENT_RUN_AS_USER));
+        security_context.insert(String::from("runAsGroup"), serde_json::json!(CLIENT_RUN_AS_GROUP));
+    }
+
+    serde_json::from_value(serde_json::json!({
+        "name": step_container_name(job_cfg.step_number),
+        "image": process.image,
+        "securityContext": match security_context.is_empty() {
+            true => serde_json::Value::Null,
+            false => serde_json::Value::Object(security_context),
+        },
+        "env": process.env,
+        "volumeMounts": [{
+            "mountPath": WORKSPACE_MOUNT_PATH,
+            "name": WORKSPACE_VOLUME_NAME
+        }],
+        "command": process.command,
+        "args": process.args,
+        "workingDir": process.working_dir
+    }))
+}
+
+// what a step container runs and the security contexts it runs with, wherever it is placed
+struct StepProcess {
+    image: String,
+    command: Option<Vec<String>>,
+    args: Vec<String>,
+    env: Vec<serde_json::Value>,
+    working_dir: String,
+    pod_security_context: serde_json::Value,
+    container_security_context: serde_json::Value,
+}
+
+fn step_process(job_cfg: &PipelineJobConfig, settings: &KubeSettings) -> StepProcess {
+    let mut env: Vec<serde_json::Value> = job_cfg.environment.iter().map(|(name, value)| serde_json::json!({ "name": name, "value": value })).collect();
+    if job_cfg.annotations.as_ref().map(|x| x.has_docker_config()).unwrap_or(false) {
+        env.push(serde_json::json!({ "name": "DOCKER_CONFIG", "value": DOCKER_CONFIG_DIRECTORY }));
+    }
+
+    // image builds run the entrypoint of the builder image instead of a shell
+    let (image, command, args, (pod_security_context, container_security_context)) = match &job_cfg.image_build {
+        Some(build) => (
+            settings.image_builder.clone(),
+            None,
+            image_build_args(build, &job_cfg.pipeline_working_directory, &image_digest_file(Path::new(WORKSPACE_MOUNT_PATH), job_cfg.step_id)),
+            settings.step_security.image_build_security_contexts(),
+        ),
+        None => (
+            job_cfg.container.clone(),
+            Some(vec![String::from("/bin/sh")]),
+            job_cfg.commands.clone(),
+            match job_cfg.elevated {
+                true => (serde_json::Value::Null, serde_json::Value::Null),
+                false => (settings.step_security.pod_security_context(), settings.step_security.container_security_context()),
+            },
+        ),
+    };
+
+    StepProcess {
+        image,
+        command,
+        args,
+        env,
+        working_dir: format!("{}", job_cfg.pipeline_working_directory.display()),
+        pod_security_context,
+        container_security_context,
+    }
+}
+
+// kaniko arguments that build the image, push it to every tag and list what was pushed in the digest file
+pub fn image_build_args(build: &ImageBuildConfig, working_directory: &Path, digest_file: &Path) -> Vec<String> {
+    let mut args = vec![
+        format!("--dockerfile={}", build.dockerfile_path(working_directory).display()),
+        format!("--context=dir://{}", build.context_path(working_directory).display()),
+        format!("--image-name-with-digest-file={}", digest_file.display()),
+    ];
+    args.extend(build.tags.iter().map(|x| format!("--destination={x}")));
+    // sorted so the same step always produces the same job
+    let mut build_args: Vec<_> = build.build_args.iter().collect();
+    build_args.sort();
+    args.extend(build_args.into_iter().map(|(name, value)| format!("--build-arg={name}={value}")));
+    args
+}
+
+pub async fn put_pod_logs_to_s3(job_name: String, container_name: Option<String>, file_name: String, s3_bucket: Bucket, namespace: &str) -> Result<Vec<String>, ConstructumKubeError> {
     let k8s_client = kube::Client::try_default().await.expect("failed to acquire k8s client");
-    let pods: Api<Pod> = Api::namespaced(k8s_client, "constructum");
+    let pods: Api<Pod> = Api::namespaced(k8s_client, namespace);
     let params = ListParams::default().labels(&format!("job-name={job_name}"));
     let mut log_names = Vec::new();
     for pod in pods.list(&params).await? {
@@ -187,14 +343,12 @@ pub async fn put_pod_logs_to_s3(job_name: String, container_name: Option<String>
 }
 
 pub struct PodLog {
-    pub job_name: String,
-    pub container_name: Option<String>,
     pub log: Bytes,
 }
 
-pub async fn stream_pod_logs(job_name: String, container_name: Option<String>) -> Result<Pin<Box<dyn Stream<Item = Result<PodLog, kube::Error>> + Send>>, ConstructumKubeError> {
+pub async fn stream_pod_logs(job_name: String, container_name: Option<String>, namespace: &str) -> Result<Pin<Box<dyn Stream<Item = Result<PodLog, kube::Error>> + Send>>, ConstructumKubeError> {
     let k8s_client = kube::Client::try_default().await.expect("failed to acquire k8s client");
-    let pods: Api<Pod> = Api::namespaced(k8s_client, "constructum");
+    let pods: Api<Pod> = Api::namespaced(k8s_client, namespace);
     let params = ListParams::default().labels(&format!("job-name={job_name}"));
     let stream: Pin<Box<dyn Stream<Item = Result<PodLog, kube::Error>> + Send>> = Box::pin(tokio_stream::empty());
     #[allow(clippy::never_loop)]
@@ -204,52 +358,93 @@ pub async fn stream_pod_logs(job_name: String, container_name: Option<String>) -
         await_condition(pods.clone(), &pod_name, conditions::is_pod_running()).await?;
         // this may fail due to k8s errors?
         let lp = LogParams { container: container_name.clone(), follow: true, previous: false, ..Default::default() };
-        let cnt_name = container_name.clone();
-        let jname = job_name.clone();
-        let log_stream = Box::pin(pods.log_stream(&pod_name, &lp).await?.map(move |x: Result<Bytes, kube::Error>| 
-            match x {
-                Ok(log) => Ok(PodLog {
-                    job_name: jname.clone(),
-                    container_name: cnt_name.clone(),
-                    log,
-                }),
-                Err(e) => Err(e)
-        }));
+        let log_stream = Box::pin(pods.log_stream(&pod_name, &lp).await?.map(|x: Result<Bytes, kube::Error>| x.map(|log| PodLog { log })));
         return Ok(log_stream)
         // stream = Box::pin(stream.merge(log_stream));
     }
     Ok(stream)
 }
 
-pub async fn delete_job(job_name: &str) -> Result<(), ConstructumKubeError> {
+pub async fn delete_job(job_name: &str, namespace: &str) -> Result<(), ConstructumKubeError> {
     let k8s_client = kube::Client::try_default().await?;
 
-    let jobs: Api<Job> = Api::namespaced(k8s_client.clone(), "constructum");
-    let pods: Api<Pod> = Api::namespaced(k8s_client, "constructum");
+    let jobs: Api<Job> = Api::namespaced(k8s_client.clone(), namespace);
+    let pods: Api<Pod> = Api::namespaced(k8s_client, namespace);
 
     let params = ListParams::default().labels(&format!("job-name={job_name}"));
     for pod in pods.list(&params).await? {
-        pods.delete(&pod.metadata.name.expect("failed to pull pod name"), &DeleteParams::background()).await?;
+        ignore_not_found(pods.delete(&pod.metadata.name.expect("failed to pull pod name"), &DeleteParams::background()).await)?;
     }
 
-    jobs.delete(job_name, &DeleteParams::background()).await?;
+    // something else (ttl, reconcile, cancellation) may have removed the job already
+    ignore_not_found(jobs.delete(job_name, &DeleteParams::background()).await)?;
 
     Ok(())
 }
 
-pub async fn delete_pvc(pipeline_uuid: &str) -> Result<(), ConstructumKubeError> {
+fn ignore_not_found<T>(result: Result<T, kube::Error>) -> Result<(), kube::Error> {
+    match result {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(err)) if err.code == 404 => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+// every client and step job constructum has in the cluster
+pub async fn list_pipeline_jobs(namespace: &str) -> Result<Vec<Job>, ConstructumKubeError> {
+    let k8s_client = kube::Client::try_default().await?;
+
+    let jobs: Api<Job> = Api::namespaced(k8s_client, namespace);
+
+    let params = ListParams::default().labels(&managed_selector());
+    Ok(jobs.list(&params).await?.items)
+}
+
+pub async fn list_pipeline_pvcs(namespace: &str) -> Result<Vec<PersistentVolumeClaim>, ConstructumKubeError> {
     let k8s_client = kube::Client::try_default().await?;
 
-    let pvcs: Api<PersistentVolumeClaim> = Api::namespaced(k8s_client, "constructum");
+    let pvcs: Api<PersistentVolumeClaim> = Api::namespaced(k8s_client, namespace);
+
+    let params = ListParams::default().labels(&managed_selector());
+    Ok(pvcs.list(&params).await?.items)
+}
 
-    let params = ListParams::default().labels(&format!("constructum-pipeline={pipeline_uuid}"));
+// deletes the client job and every step job of a pipeline
+pub async fn delete_pipeline_jobs(pipeline_uuid: &str, namespace: &str) -> Result<(), ConstructumKubeError> {
+    let k8s_client = kube::Client::try_default().await?;
+
+    let jobs: Api<Job> = Api::namespaced(k8s_client, namespace);
+
+    let params = ListParams::default().labels(&job_selector(pipeline_uuid));
+    for job in jobs.list(&params).await? {
+        delete_job(&job.metadata.name.expect("failed to pull job name"), namespace).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn delete_pvc(pipeline_uuid: &str, namespace: &str) -> Result<(), ConstructumKubeError> {
+    let k8s_client = kube::Client::try_default().await?;
+
+    let pvcs: Api<PersistentVolumeClaim> = Api::namespaced(k8s_client, namespace);
+
+    let params = ListParams::default().labels(&component_selector(pipeline_uuid, COMPONENT_WORKSPACE));
     for pvc in pvcs.list(&params).await? {
-        pvcs.delete(&pvc.metadata.name.expect("failed to pull pvc name"), &DeleteParams::background()).await?;
+        ignore_not_found(pvcs.delete(&pvc.metadata.name.expect("failed to pull pvc name"), &DeleteParams::background()).await)?;
     }
 
     Ok(())
 }
 
+pub async fn pvc_exists(pipeline_uuid: &str, namespace: &str) -> Result<bool, ConstructumKubeError> {
+    let k8s_client = kube::Client::try_default().await?;
+
+    let pvcs: Api<PersistentVolumeClaim> = Api::namespaced(k8s_client, namespace);
+
+    let params = ListParams::default().labels(&component_selector(pipeline_uuid, COMPONENT_WORKSPACE));
+    Ok(!pvcs.list(&params).await?.items.is_empty())
+}
+
 pub async fn read_kubernetes_token(vault_url: String, location: PathBuf) -> Result<String, PipelineExecError> {
     let mut fs = File::open(location).await?;
 
@@ -0,0 +1,171 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+
+use futures::StreamExt;
+use k8s_openapi::api::{batch::v1::Job, core::v1::{ContainerStatus, Pod}};
+use kube::{Api, api::ListParams, runtime::{watcher, wait::{conditions, Condition}}};
+use tracing::warn;
+
+use super::{error::ConstructumKubeError, utils::is_job_failed, KubeSettings};
+
+// container waiting reasons a pod does not get out of without someone fixing the job
+const STUCK_REASONS: [&str; 3] = ["ImagePullBackOff", "ErrImagePull", "CreateContainerConfigError"];
+
+// the watcher starts over by relisting after an error, this keeps it from hammering the api server while that fails
+const WATCH_RETRY_SECS: u64 = 1;
+
+// pods that wait for nothing but time to become schedulable do not change, so they are looked at again this often
+const UNSCHEDULABLE_RECHECK_SECS: u64 = 15;
+
+#[derive(Debug, PartialEq)]
+pub enum JobWaitOutcome {
+    Finished,
+    Deleted,
+    // a pod of the job cannot start, with the reason why
+    Stuck(String),
+}
+
+// waits until the job completes, fails or is deleted, giving up early once one of its pods cannot start
+pub async fn await_job(job_name: &str, settings: &KubeSettings) -> Result<JobWaitOutcome, ConstructumKubeError> {
+    let namespace = &settings.namespace;
+    let grace = chrono::Duration::seconds(i64::try_from(settings.unschedulable_grace_seconds).unwrap_or(i64::MAX));
+    let k8s_client = kube::Client::try_default().await?;
+    let jobs: Api<Job> = Api::namespaced(k8s_client.clone(), namespace);
+    let pods: Api<Pod> = Api::namespaced(k8s_client, namespace);
+
+    let mut job_events = watcher(jobs, ListParams::default().fields(&format!("metadata.name={job_name}"))).boxed();
+    let mut pod_events = watcher(pods, ListParams::default().labels(&format!("job-name={job_name}"))).boxed();
+
+    // the latest state of every pod of the job, by name
+    let mut job_pods: HashMap<String, Pod> = HashMap::new();
+    let mut recheck = tokio::time::interval(Duration::from_secs(UNSCHEDULABLE_RECHECK_SECS));
+
+    loop {
+        tokio::select! {
+            event = job_events.next() => match event {
+                Some(Ok(watcher::Event::Applied(job))) if is_job_finished(&job) => return Ok(JobWaitOutcome::Finished),
+                Some(Ok(watcher::Event::Applied(_))) => {},
+                Some(Ok(watcher::Event::Deleted(_))) => return Ok(JobWaitOutcome::Deleted),
+                // the job exists before anyone waits on it, so an empty listing means it is gone
+                Some(Ok(watcher::Event::Restarted(jobs))) => match jobs.first() {
+                    Some(job) if is_job_finished(job) => return Ok(JobWaitOutcome::Finished),
+                    Some(_) => {},
+                    None => return Ok(JobWaitOutcome::Deleted),
+                },
+                Some(Err(err)) => watch_failed(&format!("job {job_name}"), err).await,
+                None => return Err(ConstructumKubeError::WatchEnded(format!("job {job_name}"))),
+            },
+            event = pod_events.next() => match event {
+                Some(Ok(watcher::Event::Applied(pod))) => {
+                    job_pods.insert(pod.metadata.name.clone().unwrap_or_default(), pod);
+                },
+                Some(Ok(watcher::Event::Deleted(pod))) => {
+                    job_pods.remove(&pod.metadata.name.clone().unwrap_or_default());
+                },
+                Some(Ok(watcher::Event::Restarted(pods))) => {
+                    job_pods = pods.into_iter().map(|x| (x.metadata.name.clone().unwrap_or_default(), x)).collect();
+                },
+                Some(Err(err)) => watch_failed(&format!("pods of job {job_name}"), err).await,
+                None => return Err(ConstructumKubeError::WatchEnded(format!("pods of job {job_name}"))),
+            },
+            _ = recheck.tick() => {},
+        }
+
+        if let Some(reason) = job_pods.values().find_map(|x| stuck_reason(x, grace, Utc::now())) {
+            return Ok(JobWaitOutcome::Stuck(reason));
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ContainerStage {
+    Started,
+    Terminated,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ContainerWaitOutcome {
+    Running,
+    // with the exit code of the container
+    Terminated(i32),
+    // the container cannot start, with the reason why
+    Stuck(String),
+    PodDeleted,
+}
+
+// waits until an ephemeral container of the pod has reached the given stage, giving up early if it cannot start
+pub async fn await_ephemeral_container(pod_name: &str, container_name: &str, stage: ContainerStage, namespace: &str) -> Result<ContainerWaitOutcome, ConstructumKubeError> {
+    let k8s_client = kube::Client::try_default().await?;
+    let pods: Api<Pod> = Api::namespaced(k8s_client, namespace);
+
+    let mut pod_events = watcher(pods, ListParams::default().fields(&format!("metadata.name={pod_name}"))).boxed();
+
+    loop {
+        let pod = match pod_events.next().await {
+            Some(Ok(watcher::Event::Applied(pod))) => pod,
+            Some(Ok(watcher::Event::Deleted(_))) => return Ok(ContainerWaitOutcome::PodDeleted),
+            Some(Ok(watcher::Event::Restarted(pods))) => match pods.into_iter().next() {
+                Some(pod) => pod,
+                None => return Ok(ContainerWaitOutcome::PodDeleted),
+            },
+            Some(Err(err)) => {
+                watch_failed(&format!("pod {pod_name}"), err).await;
+                continue;
+            },
+            None => return Err(ConstructumKubeError::WatchEnded(format!("pod {pod_name}"))),
+        };
+
+        let container = pod.status.iter()
+            .flat_map(|x| x.ephemeral_container_statuses.iter().flatten())
+            .find(|x| x.name == container_name);
+        if let Some(outcome) = container.and_then(|x| container_outcome(x, stage)) {
+            return Ok(outcome);
+        }
+    }
+}
+
+// the watcher recovers from its own errors on the next poll, so they are only worth a log line
+async fn watch_failed(watched: &str, err: watcher::Error) {
+    warn!("watch of {watched} failed, retrying: {err}");
+    tokio::time::sleep(Duration::from_secs(WATCH_RETRY_SECS)).await;
+}
+
+fn container_outcome(container: &ContainerStatus, stage: ContainerStage) -> Option<ContainerWaitOutcome> {
+    let state = container.state.as_ref()?;
+
+    if let Some(terminated) = &state.terminated {
+        return Some(ContainerWaitOutcome::Terminated(terminated.exit_code));
+    }
+    if state.running.is_some() && stage == ContainerStage::Started {
+        return Some(ContainerWaitOutcome::Running);
+    }
+
+    container_stuck_reason(container).map(ContainerWaitOutcome::Stuck)
+}
+
+fn is_job_finished(job: &Job) -> bool {
+    conditions::is_job_completed().matches_object(Some(job)) || is_job_failed().matches_object(Some(job))
+}
+
+// pods are briefly unschedulable while their PVC binds or the autoscaler adds a node, so that only counts once it lasted past the grace period
+pub(crate) fn stuck_reason(pod: &Pod, unschedulable_grace: chrono::Duration, now: DateTime<Utc>) -> Option<String> {
+    let status = pod.status.as_ref()?;
+
+    let mut containers = status.init_container_statuses.iter().flatten().chain(status.container_statuses.iter().flatten());
+    if let Some(reason) = containers.find_map(container_stuck_reason) {
+        return Some(reason);
+    }
+
+    status.conditions.iter().flatten()
+        .filter(|x| x.type_ == "PodScheduled" && x.status == "False" && x.reason.as_deref() == Some("Unschedulable"))
+        .find(|x| x.last_transition_time.as_ref().map(|time| now - time.0 >= unschedulable_grace).unwrap_or(false))
+        .map(|x| format!("pod cannot be scheduled: {}", x.message.clone().unwrap_or_default()))
+}
+
+fn container_stuck_reason(container: &ContainerStatus) -> Option<String> {
+    let waiting = container.state.as_ref().and_then(|x| x.waiting.as_ref())?;
+    let reason = waiting.reason.as_deref().filter(|x| STUCK_REASONS.contains(x))?;
+
+    Some(format!("container {} is stuck in {reason}: {}", container.name, waiting.message.clone().unwrap_or_default()))
+}
@@ -0,0 +1,113 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+use uuid::Uuid;
+
+use crate::server::api::{job::JobInfo, repo::RepoInfo};
+
+use super::STEP_CONTAINER_NAME;
+
+// everything constructum creates carries these, so resources are looked up by what they belong to rather than by name
+pub const LABEL_MANAGED_BY: &str = "app.kubernetes.io/managed-by";
+pub const LABEL_COMPONENT: &str = "app.kubernetes.io/component";
+pub const LABEL_JOB: &str = "constructum.dev/job";
+pub const LABEL_STEP: &str = "constructum.dev/step";
+pub const LABEL_REPO: &str = "constructum.dev/repo";
+pub const LABEL_BUILD_NUMBER: &str = "constructum.dev/build-number";
+// free-form values do not fit the label syntax, so they go into annotations
+pub const ANNOTATION_REPO_NAME: &str = "constructum.dev/repo-name";
+pub const ANNOTATION_STEP_NAME: &str = "constructum.dev/step-name";
+
+pub const MANAGED_BY: &str = "constructum";
+pub const COMPONENT_CLIENT: &str = "client";
+pub const COMPONENT_STEP: &str = "step";
+pub const COMPONENT_WORKSPACE: &str = "workspace";
+
+// 12 hex digits keep every name far below the 63 character label limit while staying unique in practice
+fn short_id(uuid: Uuid) -> String {
+    uuid.simple().to_string()[..12].to_owned()
+}
+
+pub fn client_job_name(job_uuid: Uuid) -> String {
+    format!("cst-{}-client", short_id(job_uuid))
+}
+
+// retries of a step after an infrastructure failure get a Job of their own
+pub fn step_job_name(job_uuid: Uuid, step_number: i32, attempt: u32) -> String {
+    match attempt {
+        0 | 1 => format!("cst-{}-step-{step_number}", short_id(job_uuid)),
+        attempt => format!("cst-{}-step-{step_number}-a{attempt}", short_id(job_uuid)),
+    }
+}
+
+// steps that run inside the client pod all need their own container name there
+pub fn step_container_name(step_number: i32) -> String {
+    format!("{STEP_CONTAINER_NAME}-{step_number}")
+}
+
+pub fn workspace_pvc_name(workspace_uuid: Uuid) -> String {
+    format!("cst-{}-workspace", short_id(workspace_uuid))
+}
+
+// where an image build step lists the images it pushed, in the workspace the step and the client share
+pub fn image_digest_file(workspace_root: &Path, step_id: Uuid) -> PathBuf {
+    workspace_root.join(format!(".constructum-images-{step_id}"))
+}
+
+pub fn managed_selector() -> String {
+    format!("{LABEL_MANAGED_BY}={MANAGED_BY}")
+}
+
+pub fn job_selector(job_uuid: &str) -> String {
+    format!("{LABEL_MANAGED_BY}={MANAGED_BY},{LABEL_JOB}={job_uuid}")
+}
+
+pub fn component_selector(job_uuid: &str, component: &str) -> String {
+    format!("{},{LABEL_COMPONENT}={component}", job_selector(job_uuid))
+}
+
+// the job a resource belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceIdentity {
+    pub job_uuid: Uuid,
+    pub repo_uuid: Uuid,
+    // owner/name
+    pub repo_name: String,
+    pub build_number: i32,
+}
+
+impl ResourceIdentity {
+    pub fn new(job_info: &JobInfo, repo_info: &RepoInfo) -> ResourceIdentity {
+        ResourceIdentity {
+            job_uuid: job_info.job_uuid,
+            repo_uuid: repo_info.repo_uuid,
+            repo_name: format!("{}/{}", repo_info.repo_owner, repo_info.repo_name),
+            build_number: job_info.job_number,
+        }
+    }
+
+    pub fn labels(&self, component: &str) -> BTreeMap<String, String> {
+        BTreeMap::from([
+            (String::from(LABEL_MANAGED_BY), String::from(MANAGED_BY)),
+            (String::from(LABEL_COMPONENT), String::from(component)),
+            (String::from(LABEL_JOB), self.job_uuid.to_string()),
+            (String::from(LABEL_REPO), self.repo_uuid.to_string()),
+            (String::from(LABEL_BUILD_NUMBER), self.build_number.to_string()),
+        ])
+    }
+
+    pub fn step_labels(&self, step_id: Uuid) -> BTreeMap<String, String> {
+        let mut labels = self.labels(COMPONENT_STEP);
+        labels.insert(String::from(LABEL_STEP), step_id.to_string());
+        labels
+    }
+
+    pub fn annotations(&self) -> BTreeMap<String, String> {
+        BTreeMap::from([(String::from(ANNOTATION_REPO_NAME), self.repo_name.clone())])
+    }
+
+    pub fn step_annotations(&self, step_name: &str) -> BTreeMap<String, String> {
+        let mut annotations = self.annotations();
+        annotations.insert(String::from(ANNOTATION_STEP_NAME), String::from(step_name));
+        annotations
+    }
+}
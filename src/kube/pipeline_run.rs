@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use kube::CustomResource;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+// keeps a PipelineRun around until the job it started has been cancelled
+pub const PIPELINE_RUN_FINALIZER: &str = "constructum.dev/cancel-job";
+
+// a build requested through the Kubernetes API rather than the REST API.
+// runs are immutable: changing the spec of a run that already started a job has no effect.
+#[derive(CustomResource, Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[kube(group = "constructum.dev", version = "v1alpha1", kind = "PipelineRun", namespaced, status = "PipelineRunStatus", shortname = "prun")]
+#[kube(printcolumn = r#"{"name":"Repo","type":"string","jsonPath":".spec.repo"}"#)]
+#[kube(printcolumn = r#"{"name":"Build","type":"integer","jsonPath":".status.buildNumber"}"#)]
+#[kube(printcolumn = r#"{"name":"Phase","type":"string","jsonPath":".status.phase"}"#)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineRunSpec {
+    // owner/name of a registered repository
+    pub repo: String,
+    pub commit: String,
+    #[serde(default)]
+    #[schemars(schema_with = "parameters_schema")]
+    pub parameters: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineRunStatus {
+    pub job: Option<String>,
+    pub build_number: Option<i32>,
+    // the status of the job, or Failed if the run was rejected before a job was created
+    pub phase: Option<String>,
+    pub reason: Option<String>,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub observed_generation: Option<i64>,
+}
+
+// parameters may be strings or booleans, which a structural schema can only express by not checking them
+fn parameters_schema(_: &mut SchemaGenerator) -> Schema {
+    serde_json::from_value(json!({
+        "type": "object",
+        "x-kubernetes-preserve-unknown-fields": true,
+    })).expect("failed to build parameters schema")
+}
@@ -2,9 +2,14 @@ use serde_json::{json, Map};
 
 use crate::pipeline::{MaterializedSecretConfig, MaterializedSecret};
 
+// where vault renders a registry secret, for tools that read $DOCKER_CONFIG/config.json
+pub const DOCKER_CONFIG_DIRECTORY: &str = "/vault/secrets";
+
+#[derive(Clone)]
 pub struct VaultAnnotations {
     role: String,
     secrets: Vec<MaterializedSecret>,
+    docker_config: Option<MaterializedSecret>,
 }
 
 impl VaultAnnotations {
@@ -23,6 +28,13 @@ impl VaultAnnotations {
             {{{{- end }}}}", secret.secret_path, secret.object_name.to_uppercase(), secret.secret_key)));
         }
 
+        // written out as is rather than as a script to source
+        if let Some(secret) = &self.docker_config {
+            sub_values.insert("vault.hashicorp.com/agent-inject-secret-config.json".to_string(), json!(secret.secret_path));
+            sub_values.insert("vault.hashicorp.com/agent-inject-template-config.json".to_string(),
+            json!(format!("{{{{ with secret \"constructum/{}\" -}}}}{{{{ .Data.data.{} }}}}{{{{- end }}}}", secret.secret_path, secret.secret_key)));
+        }
+
         serde_json::Value::Object(sub_values)
     }
 
@@ -34,9 +46,23 @@ impl VaultAnnotations {
         }
         src_commands
     }
+
+    // the variables the secrets end up in once sourced
+    pub fn variable_names(&self) -> Vec<String> {
+        self.secrets.iter().map(|x| x.object_name.to_uppercase()).collect()
+    }
+
+    pub fn with_docker_config(mut self, secret: MaterializedSecret) -> VaultAnnotations {
+        self.docker_config = Some(secret);
+        self
+    }
+
+    pub fn has_docker_config(&self) -> bool {
+        self.docker_config.is_some()
+    }
 }
 
 pub fn build_vault_annotations(secret_cfg: MaterializedSecretConfig) -> VaultAnnotations {
     let materialized_secret_data = secret_cfg.secrets();
-    VaultAnnotations { role: String::from("constructum"), secrets: materialized_secret_data.clone() }
+    VaultAnnotations { role: String::from("constructum"), secrets: materialized_secret_data.clone(), docker_config: None }
 }
\ No newline at end of file
@@ -0,0 +1,118 @@
+use crate::config::Config;
+
+// where and how constructum creates its resources in the cluster
+#[derive(Debug, Clone, PartialEq)]
+pub struct KubeSettings {
+    pub namespace: String,
+    pub storage_class: String,
+    pub workspace_size: String,
+    pub config_map: String,
+    pub client_service_account: String,
+    pub build_service_account: String,
+    pub step_security: StepSecurityPolicy,
+    // repos (owner/name) allowed to run elevated steps
+    pub elevated_step_repos: Vec<String>,
+    pub image_builder: String,
+    // retries of a step whose pod the cluster took away
+    pub infrastructure_retries: u32,
+    // how long a pod may stay unschedulable before its step counts as stuck
+    pub unschedulable_grace_seconds: u64,
+}
+
+// the security context every step runs with unless it is elevated
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepSecurityPolicy {
+    pub run_as_user: i64,
+    pub run_as_group: i64,
+    pub drop_capabilities: Vec<String>,
+    pub seccomp_profile: String,
+    pub read_only_root_filesystem: bool,
+}
+
+impl KubeSettings {
+    pub fn from(config: &Config) -> KubeSettings {
+        KubeSettings {
+            namespace: config.kube_namespace.clone().unwrap_or_else(|| String::from("constructum")),
+            storage_class: config.workspace_storage_class.clone().unwrap_or_else(|| String::from("nfs-ephemeral-client")),
+            workspace_size: config.workspace_size.clone().unwrap_or_else(|| String::from("2Gi")),
+            config_map: config.client_config_map.clone().unwrap_or_else(|| String::from("constructum-cfg")),
+            client_service_account: config.client_service_account.clone().unwrap_or_else(|| String::from("constructum-client-validate")),
+            build_service_account: config.build_service_account.clone().unwrap_or_else(|| String::from("constructum-client-build")),
+            step_security: StepSecurityPolicy::from(config),
+            elevated_step_repos: config.elevated_step_repos.clone().unwrap_or_default(),
+            image_builder: config.image_builder.clone().unwrap_or_else(|| String::from("gcr.io/kaniko-project/executor:v1.9.2")),
+            infrastructure_retries: config.step_infrastructure_retries.unwrap_or(2),
+            unschedulable_grace_seconds: config.unschedulable_grace_seconds.unwrap_or(300),
+        }
+    }
+
+    pub fn allows_elevated_steps(&self, repo_name: &str) -> bool {
+        self.elevated_step_repos.iter().any(|x| x == repo_name)
+    }
+}
+
+impl StepSecurityPolicy {
+    pub fn from(config: &Config) -> StepSecurityPolicy {
+        let run_as_user = config.step_run_as_user.unwrap_or(1000);
+
+        StepSecurityPolicy {
+            run_as_user,
+            run_as_group: config.step_run_as_group.unwrap_or(run_as_user),
+            drop_capabilities: config.step_drop_capabilities.clone().unwrap_or_else(|| vec![String::from("ALL")]),
+            seccomp_profile: config.step_seccomp_profile.clone().unwrap_or_else(|| String::from("RuntimeDefault")),
+            read_only_root_filesystem: config.step_read_only_root_filesystem.unwrap_or(false),
+        }
+    }
+
+    pub fn pod_security_context(&self) -> serde_json::Value {
+        serde_json::json!({
+            "runAsNonRoot": true,
+            "runAsUser": self.run_as_user,
+            "runAsGroup": self.run_as_group,
+            // lets the step write to the workspace whichever uid it runs as
+            "fsGroup": self.run_as_group,
+            "seccompProfile": self.seccomp_profile_values(),
+        })
+    }
+
+    // kaniko unpacks images as root, so image builds keep its default capabilities but cannot gain more
+    pub fn image_build_security_contexts(&self) -> (serde_json::Value, serde_json::Value) {
+        (serde_json::json!({ "seccompProfile": self.seccomp_profile_values() }), serde_json::json!({ "allowPrivilegeEscalation": false }))
+    }
+
+    pub fn container_security_context(&self) -> serde_json::Value {
+        serde_json::json!({
+            "allowPrivilegeEscalation": false,
+            "readOnlyRootFilesystem": self.read_only_root_filesystem,
+            "capabilities": {
+                "drop": self.drop_capabilities,
+            },
+        })
+    }
+
+    // localhost profiles are given as localhost/<path relative to the kubelet seccomp root>
+    fn seccomp_profile_values(&self) -> serde_json::Value {
+        match self.seccomp_profile.strip_prefix("localhost/") {
+            Some(profile) => serde_json::json!({ "type": "Localhost", "localhostProfile": profile }),
+            None => serde_json::json!({ "type": self.seccomp_profile }),
+        }
+    }
+}
+
+// whether a PVC size is a kubernetes quantity, e.g. 500Mi, 1.5Gi or 1e9. negative sizes are left out
+pub fn is_valid_workspace_size(size: &str) -> bool {
+    let number_end = size.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(size.len());
+    let (number, suffix) = size.split_at(number_end);
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+
+    let valid_number = whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) && !(whole.is_empty() && fraction.is_empty());
+    let valid_suffix = match suffix.strip_prefix(['e', 'E']) {
+        Some(exponent) => {
+            let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+            !exponent.is_empty() && exponent.chars().all(|c| c.is_ascii_digit())
+        },
+        None => ["", "m", "k", "M", "G", "T", "P", "E", "Ki", "Mi", "Gi", "Ti", "Pi", "Ei"].contains(&suffix),
+    };
+
+    valid_number && valid_suffix
+}
@@ -0,0 +1,322 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use chrono::{TimeZone, Utc};
+use k8s_openapi::api::core::v1::Pod;
+use kube::CustomResourceExt;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::pipeline::{ImageBuildConfig, PipelineJobConfig};
+
+use super::{utils::interruption_reason, build_client_job, ignore_not_found, build_pipeline_job, build_step_ephemeral_container, client_job_name, image_build_args, is_valid_workspace_size, step_job_name, stuck_reason, workspace_pvc_name, KubeSettings, PipelineRun, ResourceIdentity, StepSecurityPolicy, LABEL_COMPONENT, LABEL_JOB, LABEL_STEP};
+
+fn pod_with_status(status: serde_json::Value) -> Pod {
+    serde_json::from_value(json!({
+        "metadata": { "name": "pipeline-step-pod" },
+        "status": status,
+    })).expect("failed to build pod")
+}
+
+#[test]
+fn test_stuck_reason_detects_stuck_pods() {
+    let bad_image = pod_with_status(json!({
+        "containerStatuses": [{
+            "name": "build",
+            "image": "rust:latst",
+            "imageID": "",
+            "ready": false,
+            "restartCount": 0,
+            "state": { "waiting": { "reason": "ImagePullBackOff", "message": "Back-off pulling image \"rust:latst\"" } },
+        }],
+    }));
+    let unschedulable = pod_with_status(json!({
+        "conditions": [{ "type": "PodScheduled", "status": "False", "reason": "Unschedulable", "message": "0/3 nodes are available", "lastTransitionTime": "2023-05-01T12:00:00Z" }],
+    }));
+    let since = Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+
+    let bad_image_reason = stuck_reason(&bad_image, grace(), since).expect("failed to detect bad image");
+    assert!(bad_image_reason.contains("ImagePullBackOff"));
+    assert!(bad_image_reason.contains("rust:latst"));
+    assert_eq!(Some(String::from("pod cannot be scheduled: 0/3 nodes are available")), stuck_reason(&unschedulable, grace(), since + chrono::Duration::minutes(6)));
+}
+
+#[test]
+fn test_stuck_reason_waits_out_unschedulable_grace() {
+    // what a pod reports while its PVC is still binding
+    let binding = pod_with_status(json!({
+        "conditions": [{ "type": "PodScheduled", "status": "False", "reason": "Unschedulable", "message": "pod has unbound immediate PersistentVolumeClaims", "lastTransitionTime": "2023-05-01T12:00:00Z" }],
+    }));
+    let since = Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+
+    assert_eq!(None, stuck_reason(&binding, grace(), since + chrono::Duration::seconds(30)));
+    assert!(stuck_reason(&binding, grace(), since + chrono::Duration::minutes(5)).is_some());
+}
+
+fn grace() -> chrono::Duration {
+    chrono::Duration::minutes(5)
+}
+
+#[test]
+fn test_stuck_reason_ignores_starting_pods() {
+    let creating = pod_with_status(json!({
+        "containerStatuses": [{
+            "name": "build",
+            "image": "rust:latest",
+            "imageID": "",
+            "ready": false,
+            "restartCount": 0,
+            "state": { "waiting": { "reason": "ContainerCreating" } },
+        }],
+    }));
+
+    assert_eq!(None, stuck_reason(&creating, grace(), Utc::now()));
+    assert_eq!(None, stuck_reason(&pod_with_status(json!({ "phase": "Pending" })), grace(), Utc::now()));
+}
+
+#[test]
+fn test_interruption_reason_tells_evictions_from_script_failures() {
+    let evicted = pod_with_status(json!({ "phase": "Failed", "reason": "Evicted", "message": "The node was low on resource: memory." }));
+    let preempted = pod_with_status(json!({
+        "phase": "Failed",
+        "conditions": [{ "type": "DisruptionTarget", "status": "True", "reason": "PreemptionByKubeScheduler", "message": "preempted" }],
+    }));
+    let script_failure = pod_with_status(json!({
+        "phase": "Failed",
+        "containerStatuses": [{
+            "name": "step",
+            "image": "rust:latest",
+            "imageID": "",
+            "ready": false,
+            "restartCount": 0,
+            "state": { "terminated": { "exitCode": 101, "reason": "Error" } },
+        }],
+    }));
+    let over_memory_limit = pod_with_status(json!({
+        "phase": "Failed",
+        "containerStatuses": [{
+            "name": "step",
+            "image": "rust:latest",
+            "imageID": "",
+            "ready": false,
+            "restartCount": 0,
+            "state": { "terminated": { "exitCode": 137, "reason": "OOMKilled" } },
+        }],
+    }));
+
+    assert!(interruption_reason(&[evicted]).expect("failed to detect eviction").contains("Evicted"));
+    assert!(interruption_reason(&[preempted]).expect("failed to detect preemption").contains("PreemptionByKubeScheduler"));
+    assert!(interruption_reason(&[]).is_some());
+    assert_eq!(None, interruption_reason(&[script_failure]));
+    assert_eq!(None, interruption_reason(&[over_memory_limit]));
+}
+
+#[test]
+fn test_workspace_size_validation() {
+    assert!(is_valid_workspace_size("2Gi"));
+    assert!(is_valid_workspace_size("500M"));
+    assert!(is_valid_workspace_size("1073741824"));
+    assert!(is_valid_workspace_size("1.5Gi"));
+    assert!(is_valid_workspace_size(".5Gi"));
+    assert!(is_valid_workspace_size("500m"));
+    assert!(is_valid_workspace_size("1e9"));
+    assert!(!is_valid_workspace_size("."));
+    assert!(!is_valid_workspace_size("1.2.3Gi"));
+    assert!(!is_valid_workspace_size("1e"));
+    assert!(!is_valid_workspace_size("Gi"));
+    assert!(!is_valid_workspace_size("2 Gi"));
+    assert!(!is_valid_workspace_size("2GB"));
+    assert!(!is_valid_workspace_size(""));
+}
+
+#[test]
+fn test_resource_names_are_short_and_deterministic() {
+    let job_uuid = Uuid::parse_str("8f14e45f-ceea-467f-a0e6-c2e1f1b7f3c1").expect("failed to parse uuid");
+
+    assert_eq!("cst-8f14e45fceea-client", client_job_name(job_uuid));
+    assert_eq!("cst-8f14e45fceea-step-12", step_job_name(job_uuid, 12, 1));
+    assert_eq!("cst-8f14e45fceea-step-12-a3", step_job_name(job_uuid, 12, 3));
+    assert_eq!("cst-8f14e45fceea-workspace", workspace_pvc_name(job_uuid));
+    assert!(step_job_name(job_uuid, i32::MAX, u32::MAX).len() <= 63);
+}
+
+#[test]
+fn test_step_labels_identify_the_step() {
+    let identity = ResourceIdentity {
+        job_uuid: Uuid::new_v4(),
+        repo_uuid: Uuid::new_v4(),
+        repo_name: String::from("owner/repo"),
+        build_number: 7,
+    };
+    let step_id = Uuid::new_v4();
+
+    let labels = identity.step_labels(step_id);
+    assert_eq!(Some(&identity.job_uuid.to_string()), labels.get(LABEL_JOB));
+    assert_eq!(Some(&step_id.to_string()), labels.get(LABEL_STEP));
+    assert_eq!(Some(&String::from("step")), labels.get(LABEL_COMPONENT));
+    assert!(labels.values().all(|x| x.len() <= 63));
+}
+
+fn test_settings() -> KubeSettings {
+    KubeSettings {
+        namespace: String::from("constructum"),
+        storage_class: String::from("nfs-ephemeral-client"),
+        workspace_size: String::from("2Gi"),
+        config_map: String::from("constructum-cfg"),
+        client_service_account: String::from("constructum-client-validate"),
+        build_service_account: String::from("constructum-client-build"),
+        step_security: StepSecurityPolicy {
+            run_as_user: 1000,
+            run_as_group: 1000,
+            drop_capabilities: vec![String::from("ALL")],
+            seccomp_profile: String::from("localhost/profiles/build.json"),
+            read_only_root_filesystem: true,
+        },
+        elevated_step_repos: vec![String::from("owner/repo")],
+        image_builder: String::from("gcr.io/kaniko-project/executor:v1.9.2"),
+        infrastructure_retries: 2,
+        unschedulable_grace_seconds: 300,
+    }
+}
+
+fn test_step(elevated: bool) -> PipelineJobConfig {
+    PipelineJobConfig {
+        pipeline: Uuid::new_v4(),
+        workspace: Uuid::new_v4(),
+        step_id: Uuid::new_v4(),
+        step_number: 0,
+        attempt: 1,
+        step: String::from("build"),
+        container: String::from("rust:latest"),
+        commands: vec![String::from("-c"), String::from("cargo build;")],
+        pipeline_working_directory: PathBuf::from("/data/repo"),
+        annotations: None,
+        environment: HashMap::new(),
+        elevated,
+        image_build: None,
+    }
+}
+
+#[test]
+fn test_steps_run_with_the_security_policy() {
+    let identity = ResourceIdentity { job_uuid: Uuid::new_v4(), repo_uuid: Uuid::new_v4(), repo_name: String::from("owner/repo"), build_number: 1 };
+    let settings = test_settings();
+
+    let (job, _, _) = build_pipeline_job(test_step(false), &identity, &settings).expect("failed to build job");
+    let pod_spec = job.spec.and_then(|x| x.template.spec).expect("job has no pod spec");
+    let pod_security = pod_spec.security_context.expect("pod has no security context");
+    let container_security = pod_spec.containers[0].security_context.clone().expect("container has no security context");
+
+    assert_eq!(Some(true), pod_security.run_as_non_root);
+    assert_eq!(Some(1000), pod_security.run_as_user);
+    assert_eq!(Some(String::from("Localhost")), pod_security.seccomp_profile.as_ref().map(|x| x.type_.clone()));
+    assert_eq!(Some(String::from("profiles/build.json")), pod_security.seccomp_profile.and_then(|x| x.localhost_profile));
+    assert_eq!(Some(false), container_security.allow_privilege_escalation);
+    assert_eq!(Some(true), container_security.read_only_root_filesystem);
+    assert_eq!(Some(vec![String::from("ALL")]), container_security.capabilities.and_then(|x| x.drop));
+
+    let (elevated_job, _, _) = build_pipeline_job(test_step(true), &identity, &settings).expect("failed to build job");
+    let elevated_spec = elevated_job.spec.and_then(|x| x.template.spec).expect("job has no pod spec");
+    assert_eq!(None, elevated_spec.security_context);
+    assert_eq!(None, elevated_spec.containers[0].security_context);
+}
+
+#[test]
+fn test_elevated_steps_need_an_allowed_repo() {
+    let settings = test_settings();
+
+    assert!(settings.allows_elevated_steps("owner/repo"));
+    assert!(!settings.allows_elevated_steps("owner/other"));
+}
+
+#[test]
+fn test_image_build_args() {
+    let build = ImageBuildConfig {
+        dockerfile: Some(String::from("docker/api.Dockerfile")),
+        context: None,
+        tags: vec![String::from("registry.example.com/api:1.0"), String::from("registry.example.com/api:latest")],
+        build_args: HashMap::from([(String::from("VERSION"), String::from("1.0")), (String::from("CHANNEL"), String::from("stable"))]),
+        registry_secret: None,
+    };
+
+    let args = image_build_args(&build, Path::new("/data/repo"), Path::new("/data/.constructum-images-step"));
+    assert_eq!(vec![
+        "--dockerfile=/data/repo/docker/api.Dockerfile",
+        "--context=dir:///data/repo",
+        "--image-name-with-digest-file=/data/.constructum-images-step",
+        "--destination=registry.example.com/api:1.0",
+        "--destination=registry.example.com/api:latest",
+        "--build-arg=CHANNEL=stable",
+        "--build-arg=VERSION=1.0",
+    ], args);
+}
+
+#[test]
+fn test_pipeline_run_crd() {
+    let crd = PipelineRun::crd();
+    let version = &crd.spec.versions[0];
+    let schema = serde_json::to_value(&version.schema).expect("failed to serialize schema");
+    let spec = &schema["openAPIV3Schema"]["properties"]["spec"];
+
+    assert_eq!(Some(String::from("pipelineruns.constructum.dev")), crd.metadata.name);
+    assert_eq!("v1alpha1", version.name);
+    assert!(version.subresources.as_ref().and_then(|x| x.status.as_ref()).is_some());
+    assert_eq!(json!(["commit", "repo"]), spec["required"]);
+    assert_eq!(json!(true), spec["properties"]["parameters"]["x-kubernetes-preserve-unknown-fields"]);
+}
+
+#[test]
+fn test_ephemeral_steps_carry_the_whole_policy() {
+    let container = build_step_ephemeral_container(&test_step(false), &test_settings()).expect("failed to build container");
+    let security = container.security_context.expect("container has no security context");
+
+    assert_eq!("step-0", container.name);
+    assert_eq!(Some(true), security.run_as_non_root);
+    assert_eq!(Some(false), security.allow_privilege_escalation);
+    assert_eq!(Some(String::from("Localhost")), security.seccomp_profile.map(|x| x.type_));
+
+    let elevated = build_step_ephemeral_container(&test_step(true), &test_settings()).expect("failed to build container");
+    assert_eq!(None, elevated.security_context);
+}
+
+#[test]
+fn test_client_job_workspace() {
+    let identity = ResourceIdentity { job_uuid: Uuid::new_v4(), repo_uuid: Uuid::new_v4(), repo_name: String::from("owner/repo"), build_number: 1 };
+    let workspace_volume = |job: k8s_openapi::api::batch::v1::Job| job.spec
+        .and_then(|x| x.template.spec)
+        .and_then(|x| x.volumes)
+        .and_then(|x| x.into_iter().find(|v| v.name == "workspace"))
+        .expect("job has no workspace volume");
+
+    let volume_job = build_client_job(&identity, Some(identity.job_uuid), String::from("constructum:latest"), &test_settings()).expect("failed to build job");
+    let pvc = workspace_volume(volume_job).persistent_volume_claim.expect("workspace is not a pvc");
+    assert_eq!(workspace_pvc_name(identity.job_uuid), pvc.claim_name);
+
+    let pod_job = build_client_job(&identity, None, String::from("constructum:latest"), &test_settings()).expect("failed to build job");
+    assert!(workspace_volume(pod_job).empty_dir.is_some());
+}
+
+#[test]
+fn test_pod_workspace_steps_run_as_the_workspace_owner() {
+    let identity = ResourceIdentity { job_uuid: Uuid::new_v4(), repo_uuid: Uuid::new_v4(), repo_name: String::from("owner/repo"), build_number: 1 };
+    let client_job = build_client_job(&identity, None, String::from("constructum:latest"), &test_settings()).expect("failed to build job");
+    let owner = client_job.spec
+        .and_then(|x| x.template.spec)
+        .and_then(|x| x.security_context)
+        .expect("client pod has no security context");
+
+    let container = build_step_ephemeral_container(&test_step(false), &test_settings()).expect("failed to build container");
+    let security = container.security_context.expect("container has no security context");
+
+    assert_eq!(owner.run_as_user, security.run_as_user);
+    assert_eq!(owner.run_as_group, security.run_as_group);
+}
+
+fn api_error(code: u16) -> kube::Error {
+    kube::Error::Api(kube::error::ErrorResponse { status: String::from("Failure"), message: String::new(), reason: String::new(), code })
+}
+
+#[test]
+fn test_deleting_a_vanished_resource_is_not_an_error() {
+    assert!(ignore_not_found::<()>(Err(api_error(404))).is_ok());
+    assert!(ignore_not_found::<()>(Err(api_error(403))).is_err());
+}
@@ -1,6 +1,12 @@
-use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::{batch::v1::Job, core::v1::Pod};
 use kube::runtime::wait::Condition;
 
+// pod reasons that mean the cluster took the pod away, not that the commands of the step failed
+const INTERRUPTED_POD_REASONS: [&str; 6] = ["Evicted", "Preempting", "NodeLost", "NodeShutdown", "Shutdown", "UnexpectedAdmissionError"];
+// container reasons that mean the same. OOMKilled is not among them: it means the step went over its own memory limit,
+// a node running out of memory evicts the pod instead
+const INTERRUPTED_CONTAINER_REASONS: [&str; 1] = ["ContainerStatusUnknown"];
+
 pub fn is_job_failed() -> impl Condition<Job> {
     |obj: Option<&Job>| {
         if let Some(job) = &obj {
@@ -14,4 +20,31 @@ pub fn is_job_failed() -> impl Condition<Job> {
         }
         false
     }
-}
\ No newline at end of file
+}
+
+// why the pods of a failed Job stopped, if it was the infrastructure rather than the step itself
+pub fn interruption_reason(pods: &[Pod]) -> Option<String> {
+    if pods.is_empty() {
+        return Some(String::from("the pod of the step was deleted"));
+    }
+
+    pods.iter().find_map(pod_interruption_reason)
+}
+
+fn pod_interruption_reason(pod: &Pod) -> Option<String> {
+    let status = pod.status.as_ref()?;
+
+    if let Some(reason) = status.reason.as_deref().filter(|x| INTERRUPTED_POD_REASONS.contains(x)) {
+        return Some(format!("pod was stopped by the cluster ({reason}): {}", status.message.clone().unwrap_or_default()));
+    }
+
+    // set by the control plane on pods it is about to take down, e.g. for preemption or node drains
+    if let Some(condition) = status.conditions.iter().flatten().find(|x| x.type_ == "DisruptionTarget" && x.status == "True") {
+        return Some(format!("pod was disrupted ({}): {}", condition.reason.clone().unwrap_or_default(), condition.message.clone().unwrap_or_default()));
+    }
+
+    status.container_statuses.iter().flatten()
+        .filter_map(|x| x.state.as_ref().and_then(|state| state.terminated.as_ref()).map(|terminated| (x, terminated)))
+        .find(|(_, terminated)| terminated.reason.as_deref().map(|x| INTERRUPTED_CONTAINER_REASONS.contains(&x)).unwrap_or(false))
+        .map(|(container, terminated)| format!("container {} was stopped by the cluster ({})", container.name, terminated.reason.clone().unwrap_or_default()))
+}
@@ -1,6 +1,7 @@
 pub mod pipeline;
 pub mod config;
 pub mod client;
+pub mod executor;
 pub mod server;
 pub mod health;
 mod git;
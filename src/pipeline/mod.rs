@@ -1,5 +1,12 @@
 mod pipeline_structs;
 mod materialized_secret;
+mod parameters;
+mod paths;
+
+#[cfg(test)]
+mod tests;
  
 pub use self::pipeline_structs::*;
-pub use self::materialized_secret::*;
\ No newline at end of file
+pub use self::materialized_secret::*;
+pub use self::parameters::*;
+pub use self::paths::*;
\ No newline at end of file
@@ -0,0 +1,103 @@
+use std::{collections::HashMap, fmt::Display, error::Error};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct PipelineParameter {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub param_type: PipelineParameterType,
+    pub description: Option<String>,
+    pub default: Option<serde_json::Value>,
+    pub choices: Option<Vec<String>>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PipelineParameterType {
+    String,
+    Bool,
+    Choice,
+}
+
+impl PipelineParameter {
+    // parameters end up as environment variables in every step, so the name has to be a valid one
+    fn has_valid_name(&self) -> bool {
+        let mut chars = self.name.chars();
+        match chars.next() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            _ => false,
+        }
+    }
+
+    fn coerce(&self, value: &serde_json::Value) -> Result<String, PipelineParameterError> {
+        match (self.param_type, value) {
+            (PipelineParameterType::String, serde_json::Value::String(s)) => Ok(s.clone()),
+            (PipelineParameterType::Bool, serde_json::Value::Bool(b)) => Ok(b.to_string()),
+            (PipelineParameterType::Choice, serde_json::Value::String(s)) => {
+                let choices = self.choices.clone().unwrap_or_default();
+                if choices.contains(s) {
+                    Ok(s.clone())
+                } else {
+                    Err(PipelineParameterError::InvalidChoice(self.name.clone(), s.clone()))
+                }
+            },
+            _ => Err(PipelineParameterError::WrongType(self.name.clone(), self.param_type)),
+        }
+    }
+}
+
+// with require_all unset, a parameter without a value or default is left out instead of failing the whole job
+pub fn resolve_parameters(declared: &[PipelineParameter], provided: &HashMap<String, serde_json::Value>, require_all: bool) -> Result<HashMap<String, String>, PipelineParameterError> {
+    for name in provided.keys() {
+        if !declared.iter().any(|x| &x.name == name) {
+            return Err(PipelineParameterError::Unknown(name.clone()));
+        }
+    }
+
+    let mut resolved = HashMap::new();
+    for param in declared {
+        if !param.has_valid_name() {
+            return Err(PipelineParameterError::InvalidName(param.name.clone()));
+        }
+
+        if param.param_type == PipelineParameterType::Choice && param.choices.as_ref().map(|x| x.is_empty()).unwrap_or(true) {
+            return Err(PipelineParameterError::NoChoices(param.name.clone()));
+        }
+
+        let value = match (provided.get(&param.name), &param.default) {
+            (Some(value), _) => param.coerce(value)?,
+            (None, Some(default)) => param.coerce(default)?,
+            (None, None) if require_all => return Err(PipelineParameterError::Missing(param.name.clone())),
+            (None, None) => continue,
+        };
+        resolved.insert(param.name.clone(), value);
+    }
+
+    Ok(resolved)
+}
+
+#[derive(Debug)]
+pub enum PipelineParameterError {
+    Unknown(String),
+    Missing(String),
+    InvalidName(String),
+    NoChoices(String),
+    WrongType(String, PipelineParameterType),
+    InvalidChoice(String, String),
+}
+
+impl Display for PipelineParameterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineParameterError::Unknown(name) => write!(f, "Parameter Error: {name} is not declared by the pipeline"),
+            PipelineParameterError::Missing(name) => write!(f, "Parameter Error: {name} has no default and was not provided"),
+            PipelineParameterError::InvalidName(name) => write!(f, "Parameter Error: {name} is not a valid environment variable name"),
+            PipelineParameterError::NoChoices(name) => write!(f, "Parameter Error: choice parameter {name} declares no choices"),
+            PipelineParameterError::WrongType(name, ty) => write!(f, "Parameter Error: {name} must be of type {ty:?}"),
+            PipelineParameterError::InvalidChoice(name, val) => write!(f, "Parameter Error: {val} is not a valid choice for {name}"),
+        }
+    }
+}
+
+impl Error for PipelineParameterError {}
@@ -0,0 +1,40 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+const SKIP_DIRECTIVES: [&str; 4] = ["[skip ci]", "[ci skip]", "[no ci]", "[skip constructum]"];
+
+pub fn has_skip_directive(commit_message: &str) -> bool {
+    let lowered = commit_message.to_lowercase();
+    SKIP_DIRECTIVES.iter().any(|x| lowered.contains(x))
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    builder.build()
+}
+
+// changed_files of None means we could not work out what changed (new branch, manual trigger, ...),
+// in which case everything runs.
+pub fn matches_path_filters(paths: Option<&[String]>, paths_ignore: Option<&[String]>, changed_files: Option<&[String]>) -> Result<bool, globset::Error> {
+    let changed_files = match changed_files {
+        Some(changed_files) => changed_files,
+        None => return Ok(true),
+    };
+
+    if paths.is_none() && paths_ignore.is_none() {
+        return Ok(true);
+    }
+
+    let ignored = build_glob_set(paths_ignore.unwrap_or_default())?;
+    let mut relevant = changed_files.iter().filter(|x| !ignored.is_match(x));
+
+    match paths {
+        Some(paths) => {
+            let included = build_glob_set(paths)?;
+            Ok(relevant.any(|x| included.is_match(x)))
+        },
+        None => Ok(relevant.next().is_some()),
+    }
+}
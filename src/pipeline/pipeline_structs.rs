@@ -1,22 +1,32 @@
-use std::{path::PathBuf};
+use std::{path::{Path, PathBuf}, collections::HashMap};
 
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::kube::VaultAnnotations;
 
+use super::{PipelineParameter, PipelineParameterError};
+
 #[derive(Debug, PartialEq, Deserialize, Serialize, Clone, Copy)]
 pub enum PipelineStatus {
+    Queued,
     InProgress,
     Complete,
-    Failed
+    Failed,
+    Cancelled,
+    // ran past its deadline and was torn down by the watchdog
+    TimedOut,
 }
 
-impl<'a> From<PipelineStatus> for &'a str {
+impl From<PipelineStatus> for &'static str {
     fn from(value: PipelineStatus) -> Self {
         match value {
+            PipelineStatus::Queued => "Queued",
             PipelineStatus::InProgress => "InProgress",
             PipelineStatus::Complete => "Complete",
             PipelineStatus::Failed => "Failed",
+            PipelineStatus::Cancelled => "Cancelled",
+            PipelineStatus::TimedOut => "TimedOut",
         }    
     }
 }
@@ -24,9 +34,12 @@ impl<'a> From<PipelineStatus> for &'a str {
 impl<'a> From<&'a str> for PipelineStatus {
     fn from(value: &'a str) -> Self {
         match value {
+            "Queued" => PipelineStatus::Queued,
             "InProgress" => PipelineStatus::InProgress,
             "Complete" => PipelineStatus::Complete,
             "Failed" => PipelineStatus::Failed,
+            "Cancelled" => PipelineStatus::Cancelled,
+            "TimedOut" => PipelineStatus::TimedOut,
             _ => panic!("invalid PipelineStatus")
         }
     }
@@ -35,9 +48,12 @@ impl<'a> From<&'a str> for PipelineStatus {
 impl From<String> for PipelineStatus {
     fn from(value: String) -> Self {
         match value.as_str() {
+            "Queued" => PipelineStatus::Queued,
             "InProgress" => PipelineStatus::InProgress,
             "Complete" => PipelineStatus::Complete,
             "Failed" => PipelineStatus::Failed,
+            "Cancelled" => PipelineStatus::Cancelled,
+            "TimedOut" => PipelineStatus::TimedOut,
             _ => panic!("invalid PipelineStatus")
         }
     }
@@ -50,6 +66,12 @@ pub struct Pipeline {
 
     pub steps: Vec<PipelineStep>,
     pub secrets: Option<Vec<PipelineSecretConfig>>,
+    pub parameters: Option<Vec<PipelineParameter>>,
+    pub paths: Option<Vec<String>>,
+    pub paths_ignore: Option<Vec<String>>,
+    pub triggers: Option<Vec<PipelineTrigger>>,
+    // minutes the whole pipeline may run for, capped by the server-wide maximum
+    pub timeout: Option<u32>,
 }
 
 impl Pipeline {
@@ -58,18 +80,52 @@ impl Pipeline {
             step.normalize_name();
         }
     }
+
+    pub fn resolve_parameters(&self, provided: &HashMap<String, serde_json::Value>, require_all: bool) -> Result<HashMap<String, String>, PipelineParameterError> {
+        super::resolve_parameters(self.parameters.as_deref().unwrap_or_default(), provided, require_all)
+    }
+
+    pub fn should_run_for(&self, changed_files: Option<&[String]>) -> Result<bool, globset::Error> {
+        super::matches_path_filters(self.paths.as_deref(), self.paths_ignore.as_deref(), changed_files)
+    }
+
+    pub fn uses_secrets(&self) -> bool {
+        self.steps.iter().any(|x| !x.secret_names().is_empty())
+    }
 }
 
 #[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
 pub struct PipelineStep {
     pub name: String,
+    // image and commands are left out by steps that build an image
+    #[serde(default)]
     pub image: String,
+    #[serde(default)]
     pub pull: PipelineImagePullPref,
+    #[serde(default)]
     pub commands: Vec<String>,
     pub secrets: Option<Vec<StepSecretConfig>>,
+    pub paths: Option<Vec<String>>,
+    pub paths_ignore: Option<Vec<String>>,
+    // runs with the security settings of its image instead of the step security policy, if the repo is allowed to
+    #[serde(default)]
+    pub elevated: bool,
+    // builds and pushes an image instead of running commands
+    pub build_image: Option<ImageBuildConfig>,
 }
 
 impl PipelineStep {
+    // names of the pipeline secrets the step uses
+    pub fn secret_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.secrets.iter().flatten().map(|x| x.name.clone()).collect();
+        names.extend(self.build_image.as_ref().and_then(|x| x.registry_secret.clone()));
+        names
+    }
+
+    pub fn should_run_for(&self, changed_files: Option<&[String]>) -> Result<bool, globset::Error> {
+        super::matches_path_filters(self.paths.as_deref(), self.paths_ignore.as_deref(), changed_files)
+    }
+
     fn normalize_name(&mut self) {
         let norm_one = self.name.trim().to_lowercase();
         let mut normalized = String::new();
@@ -88,24 +144,78 @@ impl PipelineStep {
     }
 }
 
+// a job to start in another registered repository once this pipeline completes successfully
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct PipelineTrigger {
+    // `owner/name` of the downstream repository
+    pub repo: String,
+    // the downstream repository's default branch is used if neither is given
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+}
+
+// an image built from a Dockerfile in the repository and pushed to every tag
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct ImageBuildConfig {
+    // relative to the repository; defaults to Dockerfile in the context
+    pub dockerfile: Option<String>,
+    // relative to the repository; defaults to the repository itself
+    pub context: Option<String>,
+    // full references to push to, e.g. registry.example.com/team/app:1.0
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub build_args: HashMap<String, String>,
+    // pipeline secret holding a docker config.json with credentials for the registry
+    pub registry_secret: Option<String>,
+}
+
+impl ImageBuildConfig {
+    pub fn context_path(&self, working_directory: &Path) -> PathBuf {
+        match &self.context {
+            Some(context) => working_directory.join(context),
+            None => working_directory.to_path_buf(),
+        }
+    }
+
+    pub fn dockerfile_path(&self, working_directory: &Path) -> PathBuf {
+        match &self.dockerfile {
+            Some(dockerfile) => working_directory.join(dockerfile),
+            None => self.context_path(working_directory).join("Dockerfile"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
 pub struct StepSecretConfig {
     pub name: String,
     pub var_name: String,
 }
 
-#[derive(Debug, PartialEq, Deserialize, Serialize, Clone, Copy)]
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone, Copy, Default)]
 pub enum PipelineImagePullPref {
+    #[default]
     Always,
 }
 
+#[derive(Clone)]
 pub struct PipelineJobConfig {
-    pub pipeline: String,
+    pub pipeline: Uuid,
+    // uuid of the job whose PVC the step mounts
+    pub workspace: Uuid,
+    pub step_id: Uuid,
+    pub step_number: i32,
+    // starts at 1 and goes up whenever the step is retried after an infrastructure failure
+    pub attempt: u32,
     pub step: String,
     pub container: String,
     pub commands: Vec<String>,
     pub pipeline_working_directory: PathBuf,
     pub annotations: Option<VaultAnnotations>,
+    pub environment: HashMap<String, String>,
+    pub elevated: bool,
+    pub image_build: Option<ImageBuildConfig>,
 }
 
 #[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
@@ -0,0 +1,118 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use super::{resolve_parameters, has_skip_directive, matches_path_filters, PipelineParameter, PipelineParameterError, PipelineParameterType, PipelineStep, Pipeline};
+
+fn declared_parameters() -> Vec<PipelineParameter> {
+    vec![
+        PipelineParameter { name: "TARGET".to_string(), param_type: PipelineParameterType::Choice, description: None, default: Some(serde_json::json!("staging")), choices: Some(vec!["staging".to_string(), "prod".to_string()]) },
+        PipelineParameter { name: "DRY_RUN".to_string(), param_type: PipelineParameterType::Bool, description: None, default: Some(serde_json::json!(true)), choices: None },
+        PipelineParameter { name: "VERSION".to_string(), param_type: PipelineParameterType::String, description: None, default: None, choices: None },
+    ]
+}
+
+#[test]
+fn test_resolve_parameters_applies_defaults() {
+    let provided = HashMap::from([("VERSION".to_string(), serde_json::json!("1.2.3"))]);
+
+    let resolved = resolve_parameters(&declared_parameters(), &provided, true).expect("failed to resolve parameters");
+
+    assert_eq!(resolved.get("TARGET").map(String::as_str), Some("staging"));
+    assert_eq!(resolved.get("DRY_RUN").map(String::as_str), Some("true"));
+    assert_eq!(resolved.get("VERSION").map(String::as_str), Some("1.2.3"));
+}
+
+#[test]
+fn test_resolve_parameters_rejects_bad_input() {
+    let missing = resolve_parameters(&declared_parameters(), &HashMap::new(), true);
+    assert!(matches!(missing, Err(PipelineParameterError::Missing(_))));
+
+    let provided = HashMap::from([
+        ("VERSION".to_string(), serde_json::json!("1.2.3")),
+        ("TARGET".to_string(), serde_json::json!("qa")),
+    ]);
+    let bad_choice = resolve_parameters(&declared_parameters(), &provided, true);
+    assert!(matches!(bad_choice, Err(PipelineParameterError::InvalidChoice(_, _))));
+
+    let provided = HashMap::from([
+        ("VERSION".to_string(), serde_json::json!("1.2.3")),
+        ("DRY_RUN".to_string(), serde_json::json!("yes")),
+    ]);
+    let wrong_type = resolve_parameters(&declared_parameters(), &provided, true);
+    assert!(matches!(wrong_type, Err(PipelineParameterError::WrongType(_, _))));
+
+    let provided = HashMap::from([
+        ("VERSION".to_string(), serde_json::json!("1.2.3")),
+        ("UNDECLARED".to_string(), serde_json::json!("x")),
+    ]);
+    let unknown = resolve_parameters(&declared_parameters(), &provided, true);
+    assert!(matches!(unknown, Err(PipelineParameterError::Unknown(_))));
+}
+
+#[test]
+fn test_resolve_parameters_leaves_out_missing_values_when_not_required() {
+    let resolved = resolve_parameters(&declared_parameters(), &HashMap::new(), false).expect("failed to resolve parameters");
+
+    assert_eq!(resolved.get("TARGET").map(String::as_str), Some("staging"));
+    assert!(!resolved.contains_key("VERSION"));
+}
+
+#[test]
+fn test_path_filters() {
+    let changed: Vec<String> = vec!["README.md", "docs/intro.md"].into_iter().map(String::from).collect();
+    let paths: Vec<String> = vec!["services/api/**".to_string()];
+    let paths_ignore: Vec<String> = vec!["**/*.md".to_string()];
+
+    assert!(!matches_path_filters(Some(paths.as_slice()), None, Some(changed.as_slice())).expect("bad filter"));
+    assert!(!matches_path_filters(None, Some(paths_ignore.as_slice()), Some(changed.as_slice())).expect("bad filter"));
+    assert!(matches_path_filters(None, None, Some(changed.as_slice())).expect("bad filter"));
+    assert!(matches_path_filters(Some(paths.as_slice()), Some(paths_ignore.as_slice()), None).expect("bad filter"));
+
+    let changed: Vec<String> = vec!["services/api/src/main.rs", "services/api/README.md"].into_iter().map(String::from).collect();
+    assert!(matches_path_filters(Some(paths.as_slice()), Some(paths_ignore.as_slice()), Some(changed.as_slice())).expect("bad filter"));
+}
+
+#[test]
+fn test_skip_directive() {
+    assert!(has_skip_directive("Fix typo in docs [skip ci]"));
+    assert!(has_skip_directive("[CI SKIP] bump version"));
+    assert!(!has_skip_directive("Skip flaky test in ci"));
+}
+
+#[test]
+fn test_image_build_steps_need_no_image() {
+    let step: PipelineStep = serde_yaml::from_str("
+name: image
+build_image:
+  context: services/api
+  tags: [registry.example.com/api:latest]
+  registry_secret: registry
+").expect("failed to parse step");
+
+    let build = step.build_image.clone().expect("step builds no image");
+    assert!(step.commands.is_empty());
+    assert_eq!(vec![String::from("registry")], step.secret_names());
+    assert_eq!(PathBuf::from("/data/repo/services/api/Dockerfile"), build.dockerfile_path(Path::new("/data/repo")));
+}
+
+#[test]
+fn test_uses_secrets() {
+    let plain: Pipeline = serde_yaml::from_str("
+version: 1
+steps:
+  - name: test
+    image: rust:latest
+    commands: [cargo test]
+").expect("failed to parse pipeline");
+    let with_registry_secret: Pipeline = serde_yaml::from_str("
+version: 1
+steps:
+  - name: image
+    build_image:
+      context: .
+      tags: [registry.example.com/api:latest]
+      registry_secret: registry
+").expect("failed to parse pipeline");
+
+    assert!(!plain.uses_secrets());
+    assert!(with_registry_secret.uses_secrets());
+}
@@ -1,75 +1,140 @@
-use std::{time::Duration};
+use std::time::Duration;
 
-
-use redis::AsyncCommands;
+use redis::{AsyncCommands, streams::{StreamMaxlen, StreamRangeReply}};
 use tokio_stream::StreamExt;
-use bytes::Bytes;
 use tracing::{error, info};
+use uuid::Uuid;
 
-use crate::kube::stream_pod_logs;
+use crate::{config::Config, kube::stream_pod_logs};
 
 use self::error::ConstructumRedisError;
 
 pub mod error;
 
-pub async fn logs_to_redis(redis_client: redis::Client, job_name: String, container_name: String, phase_name: String) -> Result<(), ConstructumRedisError> {
+#[cfg(test)]
+mod tests;
+
+// the redis client together with how long and how large live logs may get
+#[derive(Clone)]
+pub struct LiveLogs {
+    client: redis::Client,
+    // entries kept per log stream, trimmed approximately
+    max_len: usize,
+    // refreshed with every entry, so a log expires once its step has been quiet for this long
+    ttl_seconds: usize,
+}
+
+impl LiveLogs {
+    pub fn new(client: redis::Client, config: &Config) -> LiveLogs {
+        LiveLogs {
+            client,
+            max_len: config.log_stream_max_len.unwrap_or(100000),
+            ttl_seconds: config.log_ttl_seconds.unwrap_or(1800),
+        }
+    }
+}
+
+// logs are keyed by the constructum job and phase, not by the name of the k8s job they come from.
+// every chunk becomes one stream entry holding the number of its first line and its text.
+pub async fn logs_to_redis(live_logs: LiveLogs, job_uuid: Uuid, phase_name: String, k8s_job_name: String, container_name: String, namespace: String) -> Result<(), ConstructumRedisError> {
     tokio::time::sleep(Duration::from_millis(5000)).await;
-    let mut stream = stream_pod_logs(job_name, Some(container_name)).await?;
-    let mut connection = redis_client.get_async_connection().await.expect("failed to connect to redis");
-
-
-    info!("starting redis");
-    // run while channel is still open / stream is still open
-    // TODO: should not publish logs every time a new log comes in. check duration across stream awaits; buffer and only publish at a refresh interval
-    while let Some(val) = stream.next().await {
-        info!("got log");
-        match val {
-            Ok(log) => {
-                let log_key = format!("job:{}:step:{}", log.job_name, phase_name.clone());
-                let log_base = grab_existing_log_from_redis(&mut connection, log_key.clone()).await.expect("failed to get log base from redis");
-                let new_log = build_new_log(log_base, log.log).await;
-                put_log_to_redis(&mut connection, log_key.clone(), new_log).await.expect("failed to put log into redis");
+    let mut stream = stream_pod_logs(k8s_job_name, Some(container_name), &namespace).await?;
+    let mut connection = live_logs.client.get_async_connection().await?;
+
+    let log_key = log_key(job_uuid, &phase_name);
+    // a retried step keeps writing to the stream of its earlier attempts
+    let mut line = next_line_number(&mut connection, &log_key).await?;
+    let mut pending = Vec::new();
+
+    // a step that prints nothing for a while is still running, so its log must not expire in the meantime
+    let mut refresh = tokio::time::interval(Duration::from_secs((live_logs.ttl_seconds as u64 / 3).max(1)));
+
+    info!("streaming logs to {log_key}");
+    loop {
+        tokio::select! {
+            val = stream.next() => match val {
+                Some(Ok(log)) => {
+                    let text = take_complete_utf8(&mut pending, &log.log);
+                    line = append_log(&mut connection, &live_logs, &log_key, line, text).await?;
+                },
+                Some(Err(e)) => {
+                    error!("Error encountered during redis log stream: {}", e)
+                },
+                None => break,
             },
-            Err(e) => {
-                error!("Error encountered during redis log stream: {}", e)
-            }
+            _ = refresh.tick() => connection.expire(&log_key, live_logs.ttl_seconds).await?,
         }
     }
 
-    info!("done");
+    // whatever is left of a cut off character still belongs in the log
+    let rest = String::from_utf8_lossy(&pending).into_owned();
+    append_log(&mut connection, &live_logs, &log_key, line, rest).await?;
+
+    info!("done streaming logs to {log_key}");
 
     Ok(())
 }
 
-pub async fn grab_log_from_redis(redis_client: redis::Client, job_name: String, phase_name: String) -> Result<Option<String>, ConstructumRedisError> {
-    let mut connection = redis_client.get_async_connection().await.expect("failed to connect to redis");
-    let log_key = format!("job:{job_name}:step:{phase_name}");
-    if connection.exists(&log_key).await? {
-        Ok(Some(connection.get(&log_key).await?))
-    } else {
-        Ok(None)
+// the log of a step while it is still live, starting after the stream entry `after` if given.
+// returns the text and the id of the last entry read, so callers can ask for only what follows.
+pub async fn grab_log_from_redis(live_logs: LiveLogs, job_uuid: Uuid, phase_name: String, after: Option<String>) -> Result<Option<(String, Option<String>)>, ConstructumRedisError> {
+    let mut connection = live_logs.client.get_async_connection().await?;
+    let log_key = log_key(job_uuid, &phase_name);
+    if !connection.exists(&log_key).await? {
+        return Ok(None);
     }
+
+    let start = after.as_ref().map(|x| format!("({x}")).unwrap_or_else(|| String::from("-"));
+    let reply: StreamRangeReply = connection.xrange(&log_key, start, "+").await?;
+
+    let log = reply.ids.iter().filter_map(|x| x.get::<String>("log")).collect();
+    let last_id = reply.ids.last().map(|x| x.id.clone()).or(after);
+    Ok(Some((log, last_id)))
 }
 
-async fn grab_existing_log_from_redis(connection: &mut redis::aio::Connection, log_key: String) -> Result<String, redis::RedisError> {
-    if connection.exists(&log_key).await? {
-        Ok(connection.get(&log_key).await?)
-    } else {
-        Ok(String::new())
+// adds one entry to the stream and returns the number of the line after it
+async fn append_log(connection: &mut redis::aio::Connection, live_logs: &LiveLogs, log_key: &str, line: u64, text: String) -> Result<u64, redis::RedisError> {
+    if text.is_empty() {
+        return Ok(line);
     }
+
+    let lines = count_lines(&text);
+    redis::pipe()
+        .xadd_maxlen(log_key, StreamMaxlen::Approx(live_logs.max_len), "*", &[("line", line.to_string()), ("log", text)]).ignore()
+        .expire(log_key, live_logs.ttl_seconds).ignore()
+        .query_async::<_, ()>(connection).await?;
+    Ok(line + lines)
 }
 
-async fn build_new_log(mut log_base: String, new_line: Bytes) -> String {
-    let new_str = String::from_utf8(new_line.to_vec()).expect("failed to convert new line");
-    log_base.push_str(&new_str);
-    log_base
+fn log_key(job_uuid: Uuid, phase_name: &str) -> String {
+    format!("job:{job_uuid}:step:{phase_name}")
 }
 
-async fn put_log_to_redis(connection: &mut redis::aio::Connection, log_key: String, log: String) -> Result<(), redis::RedisError> {
-    // sets exp to 30m by default
-    // should prevent issues/overfilling Redis
-    connection.set_ex(log_key, log, 1800).await?;
-    Ok(())
+async fn next_line_number(connection: &mut redis::aio::Connection, log_key: &str) -> Result<u64, redis::RedisError> {
+    let reply: StreamRangeReply = connection.xrevrange_count(log_key, "+", "-", 1).await?;
+    Ok(reply.ids.first()
+        .and_then(|x| Some(x.get::<u64>("line")? + count_lines(&x.get::<String>("log")?)))
+        .unwrap_or(1))
+}
+
+fn count_lines(text: &str) -> u64 {
+    text.matches('\n').count() as u64
+}
+
+// chunks of a log stream may end in the middle of a character, which then waits in pending for the rest of it
+fn take_complete_utf8(pending: &mut Vec<u8>, chunk: &[u8]) -> String {
+    pending.extend_from_slice(chunk);
+    let complete = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        Err(err) if err.error_len().is_none() => err.valid_up_to(),
+        // bytes that can never be valid are replaced rather than held back
+        Err(_) => pending.len(),
+    };
+
+    let rest = pending.split_off(complete);
+    let text = String::from_utf8_lossy(pending).into_owned();
+    *pending = rest;
+    text
 }
 
 // pub async fn delete_log_from_redis(redis_client: redis::Client, job_name: String, phase_name: String) -> Result<(), ConstructumRedisError> {
@@ -0,0 +1,26 @@
+use super::{count_lines, take_complete_utf8};
+
+#[test]
+fn split_character_waits_for_next_chunk() {
+    let bytes = "bäd\n".as_bytes();
+    let mut pending = Vec::new();
+
+    assert_eq!(take_complete_utf8(&mut pending, &bytes[..2]), "b");
+    assert_eq!(pending, &bytes[1..2]);
+    assert_eq!(take_complete_utf8(&mut pending, &bytes[2..]), "äd\n");
+    assert!(pending.is_empty());
+}
+
+#[test]
+fn invalid_bytes_are_replaced() {
+    let mut pending = Vec::new();
+
+    assert_eq!(take_complete_utf8(&mut pending, b"a\xffb"), "a\u{fffd}b");
+    assert!(pending.is_empty());
+}
+
+#[test]
+fn lines_are_counted_by_newlines() {
+    assert_eq!(count_lines("one\ntwo\nthree"), 2);
+    assert_eq!(count_lines(""), 0);
+}
@@ -1,7 +1,9 @@
-use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+
+use sqlx::{FromRow, PgPool, types::Json};
 use uuid::Uuid;
 
-use super::JobInfo;
+use super::{pick_jobs_to_dispatch, JobInfo, QueuedJob};
 use crate::{
     pipeline::PipelineStatus,
     server::{api::step, CreateJobPayload},
@@ -64,6 +66,62 @@ pub async fn get_job(job_id: Uuid, pool: PgPool) -> Result<JobInfo, sqlx::Error>
     Ok(pipeline_info)
 }
 
+pub async fn list_child_jobs(job_id: Uuid, pool: PgPool) -> Result<Vec<JobInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.jobs WHERE parent_job = $1")
+        .bind(job_id)
+        .fetch_all(&mut sql_connection)
+        .await
+}
+
+// repositories of the job and every job upstream of it, used to stop trigger cycles
+pub async fn list_ancestor_repos(job_id: Uuid, pool: PgPool) -> Result<Vec<Uuid>, sqlx::Error> {
+    #[derive(FromRow)]
+    struct AncestorRepo {
+        repo_id: Uuid,
+    }
+
+    let mut sql_connection = pool.acquire().await?;
+    let ancestors: Vec<AncestorRepo> = sqlx::query_as("WITH RECURSIVE ancestors AS (SELECT id, repo_id, parent_job FROM constructum.jobs WHERE id = $1 UNION ALL SELECT j.id, j.repo_id, j.parent_job FROM constructum.jobs j JOIN ancestors a ON j.id = a.parent_job) SELECT repo_id FROM ancestors")
+        .bind(job_id)
+        .fetch_all(&mut sql_connection)
+        .await?;
+
+    Ok(ancestors.into_iter().map(|x| x.repo_id).collect())
+}
+
+pub async fn get_job_optional(job_id: Uuid, pool: PgPool) -> Result<Option<JobInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.jobs WHERE id = $1")
+        .bind(job_id)
+        .fetch_optional(&mut sql_connection)
+        .await
+}
+
+pub async fn list_unfinished_jobs_in_workspace(workspace: Uuid, pool: PgPool) -> Result<Vec<JobInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.jobs WHERE (id = $1 OR workspace = $1) AND is_finished = FALSE")
+        .bind(workspace)
+        .fetch_all(&mut sql_connection)
+        .await
+}
+
+// workspaces of failed jobs that may still be resumed
+pub async fn list_retained_workspaces(pool: PgPool, retention_hours: i32) -> Result<Vec<Uuid>, sqlx::Error> {
+    #[derive(FromRow)]
+    struct RetainedWorkspace {
+        workspace: Uuid,
+    }
+
+    let mut sql_connection = pool.acquire().await?;
+    let workspaces: Vec<RetainedWorkspace> = sqlx::query_as("SELECT COALESCE(workspace, id) AS workspace FROM constructum.jobs WHERE status = 'Failed' AND finished_at > now() - make_interval(hours => $1)")
+        .bind(retention_hours)
+        .fetch_all(&mut sql_connection)
+        .await?;
+
+    Ok(workspaces.into_iter().map(|x| x.workspace).collect())
+}
+
 pub async fn list_unfinished_jobs(pool: PgPool) -> Result<Vec<JobInfo>, sqlx::Error> {
     let mut pipeline_info: Vec<JobInfo> = {
         // retrieve pipeline info from Postgres
@@ -89,24 +147,84 @@ pub async fn create_job(
     build_number: i32,
     repo_uuid: Uuid,
     payload: CreateJobPayload,
+    parameters: HashMap<String, String>,
+    changed_files: Option<Vec<String>>,
 ) -> Result<(), sqlx::Error> {
     let mut sql_connection = pool.acquire().await?;
-    sqlx::query("INSERT INTO constructum.jobs (id, seq, repo_id, commit_id, is_finished, status) VALUES ($1, $2, $3, $4, FALSE, 'InProgress')")
+    sqlx::query("INSERT INTO constructum.jobs (id, seq, repo_id, commit_id, git_ref, trigger, parameters, changed_files, parent_job, rerun_of, resume_from_step, workspace, not_before, timeout_minutes, pipeline_run, is_finished, status) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, FALSE, 'Queued')")
         .bind(pipeline_uuid)
         .bind(build_number)
         .bind(repo_uuid)
         .bind(&payload.commit_hash)
+        .bind(&payload.git_ref)
+        .bind(Into::<&str>::into(payload.trigger))
+        .bind(Json(parameters))
+        .bind(changed_files)
+        .bind(payload.parent_job)
+        .bind(payload.rerun_of)
+        .bind(payload.resume_from_step)
+        .bind(payload.workspace)
+        .bind(payload.not_before)
+        .bind(payload.timeout_minutes)
+        .bind(payload.pipeline_run)
         .execute(&mut sql_connection).await?;
     Ok(())
 }
 
+// arbitrary key for the advisory lock that serializes queue dispatch across servers
+const QUEUE_LOCK_KEY: i64 = 0x636f6e7374;
+
+// moves as many queued jobs to InProgress as the concurrency limits allow, fairly across repos, and returns them
+pub async fn claim_queued_jobs(
+    pool: PgPool,
+    max_concurrent_jobs: i64,
+    max_concurrent_jobs_per_repo: i64,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    #[derive(FromRow)]
+    struct RunningCount {
+        repo_id: Uuid,
+        running: i64,
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    // the running counts are only accurate if no other server is dispatching at the same time
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(QUEUE_LOCK_KEY)
+        .execute(&mut transaction)
+        .await?;
+
+    let running: Vec<RunningCount> = sqlx::query_as("SELECT repo_id, COUNT(*) AS running FROM constructum.jobs WHERE status = 'InProgress' GROUP BY repo_id")
+        .fetch_all(&mut transaction)
+        .await?;
+
+    let queued: Vec<QueuedJob> = sqlx::query_as("SELECT j.id, j.repo_id, r.max_concurrent_jobs, r.priority, j.queued_at, j.bumped_at FROM constructum.jobs j JOIN constructum.repositories r ON r.id = j.repo_id WHERE j.status = 'Queued' AND (j.not_before IS NULL OR j.not_before <= now()) ORDER BY j.queued_at FOR UPDATE OF j SKIP LOCKED")
+        .fetch_all(&mut transaction)
+        .await?;
+
+    let running_per_repo: HashMap<Uuid, i64> = running.into_iter().map(|x| (x.repo_id, x.running)).collect();
+    let claimed = pick_jobs_to_dispatch(&queued, &running_per_repo, max_concurrent_jobs, max_concurrent_jobs_per_repo);
+
+    if !claimed.is_empty() {
+        sqlx::query("UPDATE constructum.jobs SET status = 'InProgress', started_at = now() WHERE id = ANY($1)")
+            .bind(&claimed)
+            .execute(&mut transaction)
+            .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(claimed)
+}
+
 pub async fn complete_job(
     pool: PgPool,
     status: PipelineStatus,
     job_id: Uuid,
 ) -> Result<(), sqlx::Error> {
     let mut sql_connection = pool.acquire().await?;
-    sqlx::query("UPDATE constructum.jobs SET is_finished = TRUE, status = $1 WHERE id = $2")
+    // a job that was cancelled while the client was still running keeps its Cancelled status
+    sqlx::query("UPDATE constructum.jobs SET is_finished = TRUE, status = $1, finished_at = now() WHERE id = $2 AND is_finished = FALSE")
         .bind(Into::<&str>::into(status))
         .bind(job_id)
         .execute(&mut sql_connection)
@@ -114,6 +232,83 @@ pub async fn complete_job(
     Ok(())
 }
 
+pub async fn get_job_for_pipeline_run(pool: PgPool, pipeline_run: Uuid) -> Result<Option<JobInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.jobs WHERE pipeline_run = $1")
+        .bind(pipeline_run)
+        .fetch_optional(&mut sql_connection)
+        .await
+}
+
+// running jobs that started longer ago than their deadline allows
+pub async fn list_expired_jobs(
+    pool: PgPool,
+    max_minutes: i32,
+) -> Result<Vec<JobInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.jobs WHERE status = 'InProgress' AND started_at + make_interval(mins => LEAST(COALESCE(timeout_minutes, $1), $1)) < now()")
+        .bind(max_minutes)
+        .fetch_all(&mut sql_connection)
+        .await
+}
+
+// returns false if the job had already finished
+pub async fn time_out_job(
+    pool: PgPool,
+    job_id: Uuid,
+    reason: String,
+) -> Result<bool, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    let result = sqlx::query("UPDATE constructum.jobs SET is_finished = TRUE, status = 'TimedOut', status_reason = $2, finished_at = now() WHERE id = $1 AND is_finished = FALSE")
+        .bind(job_id)
+        .bind(reason)
+        .execute(&mut sql_connection)
+        .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// moves a queued job to the front of the queue. returns false if the job is not queued
+pub async fn bump_job(
+    pool: PgPool,
+    job_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    let result = sqlx::query("UPDATE constructum.jobs SET bumped_at = now() WHERE id = $1 AND status = 'Queued'")
+        .bind(job_id)
+        .execute(&mut sql_connection)
+        .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// returns false if the job had already finished
+pub async fn cancel_job(
+    pool: PgPool,
+    job_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    let result = sqlx::query("UPDATE constructum.jobs SET is_finished = TRUE, status = 'Cancelled', finished_at = now() WHERE id = $1 AND is_finished = FALSE")
+        .bind(job_id)
+        .execute(&mut sql_connection)
+        .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// unfinished push jobs of the same ref that were created before the given job
+pub async fn list_superseded_jobs(
+    pool: PgPool,
+    repo_id: Uuid,
+    git_ref: &str,
+    job_id: Uuid,
+) -> Result<Vec<JobInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.jobs WHERE repo_id = $1 AND git_ref = $2 AND trigger = 'Push' AND is_finished = FALSE AND seq < (SELECT seq FROM constructum.jobs WHERE id = $3)")
+        .bind(repo_id)
+        .bind(git_ref)
+        .bind(job_id)
+        .fetch_all(&mut sql_connection)
+        .await
+}
+
 pub async fn get_job_log_ids(pool: PgPool, job_id: Uuid) -> Result<Vec<String>, sqlx::Error> {
     #[derive(FromRow)]
     struct JobLogId {
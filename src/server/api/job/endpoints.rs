@@ -1,7 +1,8 @@
-use axum::{extract::State, Json};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
 use uuid::Uuid;
 
-use crate::{ConstructumServerState, server::error::ConstructumServerError, s3buckets};
+use crate::{ConstructumServerState, server::{self, error::ConstructumServerError}, s3buckets};
 use super::JobInfo;
 
 pub async fn list_jobs(
@@ -23,6 +24,97 @@ pub async fn get_job(
     Ok(Json(pipeline_info))
 }
 
+pub async fn list_child_jobs(
+    State(state): State<ConstructumServerState>,
+    axum::extract::Path(job_id): axum::extract::Path<Uuid>,
+) -> Result<Json<Vec<JobInfo>>, ConstructumServerError> {
+
+    let children = super::db::list_child_jobs(job_id, state.postgres()).await?;
+
+    Ok(Json(children))
+}
+
+#[tracing::instrument(skip(state))]
+pub async fn cancel_job(
+    State(state): State<ConstructumServerState>,
+    axum::extract::Path(job_id): axum::extract::Path<Uuid>,
+) -> Result<Json<JobInfo>, ConstructumServerError> {
+    // checking for existence
+    let _job_ref = super::db::get_job_optional(job_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoJobFound)?;
+
+    if !server::cancel_job(job_id, &state).await? {
+        return Err(ConstructumServerError::JobAlreadyFinished);
+    }
+
+    // a queued job has no server_job to hand its slot on, so dispatch here
+    if let Err(err) = server::dispatch_queued_jobs(state.clone()).await {
+        tracing::error!("Failed to dispatch queued jobs: {err}");
+    }
+
+    let pipeline_info = super::db::get_job(job_id, state.postgres()).await?;
+
+    Ok(Json(pipeline_info))
+}
+
+#[tracing::instrument(skip(state))]
+pub async fn bump_job(
+    State(state): State<ConstructumServerState>,
+    axum::extract::Path(job_id): axum::extract::Path<Uuid>,
+) -> Result<Json<JobInfo>, ConstructumServerError> {
+    // checking for existence
+    let _job_ref = super::db::get_job_optional(job_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoJobFound)?;
+
+    if !super::db::bump_job(state.postgres(), job_id).await? {
+        return Err(ConstructumServerError::JobNotQueued);
+    }
+
+    // a free slot may be waiting for exactly this job
+    if let Err(err) = server::dispatch_queued_jobs(state.clone()).await {
+        tracing::error!("Failed to dispatch queued jobs: {err}");
+    }
+
+    let pipeline_info = super::db::get_job(job_id, state.postgres()).await?;
+
+    Ok(Json(pipeline_info))
+}
+
+#[tracing::instrument(skip(state))]
+pub async fn rerun_job(
+    State(state): State<ConstructumServerState>,
+    axum::extract::Path(job_id): axum::extract::Path<Uuid>,
+) -> Result<impl IntoResponse, ConstructumServerError> {
+    let original = super::db::get_job_optional(job_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoJobFound)?;
+
+    let job_uuid = server::rerun_job(original, state).await?;
+
+    Ok((StatusCode::CREATED, Json(RerunJobResponse { job_uuid })))
+}
+
+#[tracing::instrument(skip(state))]
+pub async fn resume_job(
+    State(state): State<ConstructumServerState>,
+    axum::extract::Path(job_id): axum::extract::Path<Uuid>,
+) -> Result<impl IntoResponse, ConstructumServerError> {
+    let original = super::db::get_job_optional(job_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoJobFound)?;
+
+    let job_uuid = server::resume_job(original, state).await?;
+
+    Ok((StatusCode::CREATED, Json(RerunJobResponse { job_uuid })))
+}
+
+#[derive(Serialize)]
+struct RerunJobResponse {
+    job_uuid: Uuid,
+}
+
 pub async fn get_job_logs(
     State(state): State<ConstructumServerState>,
     axum::extract::Path(job_id): axum::extract::Path<Uuid>,
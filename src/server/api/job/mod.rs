@@ -2,7 +2,10 @@ pub mod db;
 pub mod endpoints;
 mod model;
 
-use axum::routing::get;
+#[cfg(test)]
+mod tests;
+
+use axum::routing::{get, post};
 
 use crate::ConstructumServerState;
 
@@ -12,6 +15,11 @@ pub fn register_module(router: axum::Router<ConstructumServerState, axum::body::
     router
         .route("/jobs", get(self::endpoints::list_jobs))
         .route("/jobs/:job_id", get(self::endpoints::get_job))
+        .route("/jobs/:job_id/cancel", post(self::endpoints::cancel_job))
+        .route("/jobs/:job_id/bump", post(self::endpoints::bump_job))
+        .route("/jobs/:job_id/rerun", post(self::endpoints::rerun_job))
+        .route("/jobs/:job_id/resume", post(self::endpoints::resume_job))
+        .route("/jobs/:job_id/children", get(self::endpoints::list_child_jobs))
         .route("/jobs/:job_id/logs", get(self::endpoints::get_job_logs))
         .route("/jobs/:job_id/steps/:step_id/logs", get(super::step::endpoints::get_log_for_step))
 }
\ No newline at end of file
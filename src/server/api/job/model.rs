@@ -1,8 +1,11 @@
-use serde::{Serialize};
+use std::{cmp::Ordering, collections::HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 
-use sqlx::{postgres::PgRow, Row};
-use crate::{pipeline::PipelineStatus, server::api::step::model::CompletedPipelineStep};
+use sqlx::{postgres::PgRow, Row, types::Json};
+use crate::{pipeline::PipelineStatus, server::api::{repo::{RepoInfo, WorkspaceMode}, step::model::CompletedPipelineStep}};
 
 
 #[derive(Debug, Serialize)]
@@ -11,8 +14,24 @@ pub struct JobInfo {
     pub job_number: i32,
     pub repo_id: Uuid,
     pub commit_id: String,
+    pub git_ref: Option<String>,
+    pub trigger: JobTrigger,
+    pub parameters: HashMap<String, String>,
+    pub changed_files: Option<Vec<String>>,
+    pub parent_job: Option<Uuid>,
+    pub rerun_of: Option<Uuid>,
+    // steps before this one were carried over from `rerun_of` instead of executed
+    pub resume_from_step: Option<i32>,
+    // the job whose PVC this job runs in, if not its own
+    pub workspace: Option<Uuid>,
+    pub timeout_minutes: Option<i32>,
+    pub queued_at: DateTime<Utc>,
+    pub bumped_at: Option<DateTime<Utc>>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
     pub is_finished: bool,
     pub status: PipelineStatus,
+    pub status_reason: Option<String>,
     pub steps: Option<Vec<CompletedPipelineStep>>
 }
 
@@ -22,19 +41,187 @@ impl<'r> sqlx::FromRow<'r, PgRow> for JobInfo {
         let job_number: i32 = row.try_get("seq")?;
         let repo_id: Uuid = row.try_get("repo_id")?;
         let commit_id: String = row.try_get("commit_id")?;
+        let git_ref: Option<String> = row.try_get("git_ref")?;
+        let trigger: String = row.try_get("trigger")?;
+        let parameters: Json<HashMap<String, String>> = row.try_get("parameters")?;
+        let changed_files: Option<Vec<String>> = row.try_get("changed_files")?;
+        let parent_job: Option<Uuid> = row.try_get("parent_job")?;
+        let rerun_of: Option<Uuid> = row.try_get("rerun_of")?;
+        let resume_from_step: Option<i32> = row.try_get("resume_from_step")?;
+        let workspace: Option<Uuid> = row.try_get("workspace")?;
+        let timeout_minutes: Option<i32> = row.try_get("timeout_minutes")?;
+        let queued_at: DateTime<Utc> = row.try_get("queued_at")?;
+        let bumped_at: Option<DateTime<Utc>> = row.try_get("bumped_at")?;
+        let started_at: Option<DateTime<Utc>> = row.try_get("started_at")?;
+        let finished_at: Option<DateTime<Utc>> = row.try_get("finished_at")?;
         let is_finished: bool = row.try_get("is_finished")?;
         let pipeline_status: String = row.try_get("status")?;
+        let status_reason: Option<String> = row.try_get("status_reason")?;
 
         Ok(
-            JobInfo { 
+            JobInfo {
                 job_uuid: uuid,
                 job_number,
                 repo_id,
-                commit_id, 
+                commit_id,
+                git_ref,
+                trigger: JobTrigger::from(trigger),
+                parameters: parameters.0,
+                changed_files,
+                parent_job,
+                rerun_of,
+                resume_from_step,
+                workspace,
+                timeout_minutes,
+                queued_at,
+                bumped_at,
+                started_at,
+                finished_at,
                 is_finished,
                 status: PipelineStatus::from(pipeline_status),
+                status_reason,
                 steps: None
             }
         )
     }
-}
\ No newline at end of file
+}
+
+impl JobInfo {
+    pub fn workspace_uuid(&self) -> Uuid {
+        self.workspace.unwrap_or(self.job_uuid)
+    }
+
+    // a job resuming in the PVC of an earlier run stays there, whatever the repo asks for now
+    pub fn workspace_mode(&self, repo_info: &RepoInfo) -> WorkspaceMode {
+        match self.workspace {
+            Some(_) => WorkspaceMode::Volume,
+            None => repo_info.workspace_mode,
+        }
+    }
+
+    // how long the job may run for, given the server-wide maximum
+    pub fn deadline_minutes(&self, max_minutes: i32) -> i32 {
+        self.timeout_minutes.map(|x| x.min(max_minutes)).unwrap_or(max_minutes)
+    }
+}
+
+// a queued job competing for a dispatch slot
+#[derive(Debug, Clone)]
+pub struct QueuedJob {
+    pub job_uuid: Uuid,
+    pub repo_id: Uuid,
+    // overrides the server-wide per-repo concurrency limit
+    pub max_concurrent_jobs: Option<i32>,
+    pub priority: i32,
+    pub queued_at: DateTime<Utc>,
+    pub bumped_at: Option<DateTime<Utc>>,
+}
+
+impl<'r> sqlx::FromRow<'r, PgRow> for QueuedJob {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let job_uuid: Uuid = row.try_get("id")?;
+        let repo_id: Uuid = row.try_get("repo_id")?;
+        let max_concurrent_jobs: Option<i32> = row.try_get("max_concurrent_jobs")?;
+        let priority: i32 = row.try_get("priority")?;
+        let queued_at: DateTime<Utc> = row.try_get("queued_at")?;
+        let bumped_at: Option<DateTime<Utc>> = row.try_get("bumped_at")?;
+
+        Ok(QueuedJob { job_uuid, repo_id, max_concurrent_jobs, priority, queued_at, bumped_at })
+    }
+}
+
+// picks the queued jobs to start within the concurrency limits. bumped jobs go first, the most recently bumped first.
+// every other slot goes to the repo with the fewest running jobs per unit of priority, so a repo that queues
+// a lot of jobs only ever gets its share of the cluster. ties go to the oldest job.
+pub fn pick_jobs_to_dispatch(queued: &[QueuedJob], running_per_repo: &HashMap<Uuid, i64>, max_concurrent_jobs: i64, max_concurrent_jobs_per_repo: i64) -> Vec<Uuid> {
+    let mut running = running_per_repo.clone();
+    let mut running_total: i64 = running.values().sum();
+    let mut waiting: Vec<&QueuedJob> = queued.iter().collect();
+    waiting.sort_by_key(|x| x.queued_at);
+
+    let mut picked = Vec::new();
+    while running_total < max_concurrent_jobs {
+        let next = waiting.iter()
+            .enumerate()
+            .filter(|(_, job)| running.get(&job.repo_id).copied().unwrap_or(0) < job.max_concurrent_jobs.map(i64::from).unwrap_or(max_concurrent_jobs_per_repo))
+            .min_by(|(_, left), (_, right)| dispatch_order(left, right, &running))
+            .map(|(idx, _)| idx);
+
+        let job = match next {
+            Some(idx) => waiting.remove(idx),
+            None => break,
+        };
+
+        *running.entry(job.repo_id).or_insert(0) += 1;
+        running_total += 1;
+        picked.push(job.job_uuid);
+    }
+
+    picked
+}
+
+fn dispatch_order(left: &QueuedJob, right: &QueuedJob, running: &HashMap<Uuid, i64>) -> Ordering {
+    let running_of = |job: &QueuedJob| running.get(&job.repo_id).copied().unwrap_or(0);
+
+    // compares running / priority of both repos without dividing
+    let left_share = running_of(left) * i64::from(right.priority.max(1));
+    let right_share = running_of(right) * i64::from(left.priority.max(1));
+
+    // None sorts before Some, so comparing the other way round puts bumped jobs, latest bump first, in front
+    right.bumped_at.cmp(&left.bumped_at)
+        .then(left_share.cmp(&right_share))
+        .then(left.queued_at.cmp(&right.queued_at))
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone, Copy)]
+pub enum JobTrigger {
+    Push,
+    Manual,
+    Cron,
+    Upstream,
+    Rerun,
+    PipelineRun,
+}
+
+impl JobTrigger {
+    // pushes and upstream jobs have nobody to ask for a value, so only these must fill in every parameter
+    pub fn supplies_parameters(&self) -> bool {
+        matches!(self, JobTrigger::Manual | JobTrigger::Cron)
+    }
+}
+
+impl From<JobTrigger> for &'static str {
+    fn from(value: JobTrigger) -> Self {
+        match value {
+            JobTrigger::Push => "Push",
+            JobTrigger::Manual => "Manual",
+            JobTrigger::Cron => "Cron",
+            JobTrigger::Upstream => "Upstream",
+            JobTrigger::Rerun => "Rerun",
+            JobTrigger::PipelineRun => "PipelineRun",
+        }
+    }
+}
+
+impl From<String> for JobTrigger {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "Push" => JobTrigger::Push,
+            "Manual" => JobTrigger::Manual,
+            "Cron" => JobTrigger::Cron,
+            "Upstream" => JobTrigger::Upstream,
+            "Rerun" => JobTrigger::Rerun,
+            "PipelineRun" => JobTrigger::PipelineRun,
+            _ => panic!("invalid JobTrigger")
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TriggerJobPayload {
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    pub commit: Option<String>,
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, TimeZone, Utc};
+use uuid::Uuid;
+
+use super::{pick_jobs_to_dispatch, QueuedJob};
+
+fn queued_job(repo_id: Uuid, priority: i32, minutes_ago: i64) -> QueuedJob {
+    QueuedJob {
+        job_uuid: Uuid::new_v4(),
+        repo_id,
+        max_concurrent_jobs: None,
+        priority,
+        queued_at: Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap() - Duration::minutes(minutes_ago),
+        bumped_at: None,
+    }
+}
+
+#[test]
+fn test_noisy_repo_does_not_starve_others() {
+    let noisy = Uuid::new_v4();
+    let quiet = Uuid::new_v4();
+    // the noisy repo queued all of its jobs before the quiet one queued anything
+    let mut queued: Vec<QueuedJob> = (0..5).map(|x| queued_job(noisy, 1, 60 - x)).collect();
+    queued.push(queued_job(quiet, 1, 1));
+
+    let picked = pick_jobs_to_dispatch(&queued, &HashMap::new(), 2, 10);
+
+    assert_eq!(vec![queued[0].job_uuid, queued[5].job_uuid], picked);
+}
+
+#[test]
+fn test_priority_weights_share_of_slots() {
+    let important = Uuid::new_v4();
+    let other = Uuid::new_v4();
+    let queued: Vec<QueuedJob> = (0..4).flat_map(|x| [queued_job(important, 3, 60 - x), queued_job(other, 1, 60 - x)]).collect();
+
+    let picked = pick_jobs_to_dispatch(&queued, &HashMap::new(), 4, 10);
+    let picked_important = picked.iter().filter(|x| queued.iter().any(|job| job.job_uuid == **x && job.repo_id == important)).count();
+
+    assert_eq!(4, picked.len());
+    assert_eq!(3, picked_important);
+}
+
+#[test]
+fn test_bumped_jobs_go_first_within_limits() {
+    let repo = Uuid::new_v4();
+    let limited = Uuid::new_v4();
+    let mut queued = vec![queued_job(repo, 1, 30), queued_job(repo, 1, 20), queued_job(limited, 1, 10)];
+    queued[1].bumped_at = Some(Utc::now());
+    queued[2].bumped_at = Some(Utc::now());
+
+    // the limited repo already runs as many jobs as it may
+    let running = HashMap::from([(limited, 2)]);
+    let picked = pick_jobs_to_dispatch(&queued, &running, 4, 2);
+
+    assert_eq!(vec![queued[1].job_uuid, queued[0].job_uuid], picked);
+}
@@ -1,4 +1,5 @@
 pub mod job;
 pub mod repo;
+pub mod schedule;
 pub mod step;
 pub mod webhook;
\ No newline at end of file
@@ -46,6 +46,19 @@ pub async fn get_repo_by_git_id(
         .await
 }
 
+pub async fn get_repo_by_name(
+    repo_owner: &str,
+    repo_name: &str,
+    pool: PgPool
+) -> Result<Option<RepoInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.repositories WHERE repo_owner = $1 AND repo_name = $2")
+        .bind(repo_owner)
+        .bind(repo_name)
+        .fetch_optional(&mut sql_connection)
+        .await
+}
+
 #[tracing::instrument]
 pub async fn register_repo(
     pool: PgPool,
@@ -109,4 +122,25 @@ pub async fn update_repo_seq(
         .execute(&mut sql_connection)
         .await?;
     Ok(())
+}
+
+#[tracing::instrument]
+pub async fn update_repo_settings(
+    pool: PgPool,
+    repo_id: Uuid,
+    settings: &RepoInfo,
+) -> Result<(), sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query("UPDATE constructum.repositories SET auto_cancel = $2, debounce_seconds = $3, max_concurrent_jobs = $4, workspace_size = $5, storage_class = $6, priority = $7, workspace_mode = $8 WHERE id = $1")
+        .bind(repo_id)
+        .bind(settings.auto_cancel)
+        .bind(settings.debounce_seconds)
+        .bind(settings.max_concurrent_jobs)
+        .bind(&settings.workspace_size)
+        .bind(&settings.storage_class)
+        .bind(settings.priority)
+        .bind(Into::<&str>::into(settings.workspace_mode))
+        .execute(&mut sql_connection)
+        .await?;
+    Ok(())
 }
\ No newline at end of file
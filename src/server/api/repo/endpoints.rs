@@ -4,19 +4,22 @@ use axum::{
     response::IntoResponse,
     Json,
 };
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 
 use uuid::Uuid;
 
 use crate::{
+    kube::is_valid_workspace_size,
     server::{
-        api::{repo::{db::list_repos, GitRepoResponse}, job::JobInfo},
+        self,
+        api::{repo::{db::list_repos, GitRepoResponse}, job::{JobInfo, JobTrigger, TriggerJobPayload}},
         error::ConstructumServerError,
+        JobRevision,
     },
-    utils, ConstructumServerState,
+    ConstructumServerState,
 };
 
-use super::{GiteaRepository, RegisterRepositoryPayload, RepoInfo};
+use super::{GiteaRepository, RegisterRepositoryPayload, RepoInfo, RepoSettingsPayload, WorkspaceMode};
 
 pub async fn list_known_repos(
     State(state): State<ConstructumServerState>,
@@ -33,27 +36,13 @@ pub async fn list_all_repos(
     let auth_tok = headers
         .get("Authorization")
         .ok_or(ConstructumServerError::BadAuthorization)?;
-    let resp = utils::get_with_auth(
-        format!("{}/api/v1/repos/search", state.git_server_url()),
-        "Authorization",
+    let git_repos = super::system::list_forge_repositories(
+        state.git_forge(),
+        state.git_server_url(),
         auth_tok.to_str()?.to_owned(),
     )
     .await?;
 
-    #[derive(Debug, Deserialize)]
-    struct GiteaRepositoryResponse {
-        data: Vec<GiteaRepository>,
-        ok: bool,
-    }
-
-    let git_repos_resp: GiteaRepositoryResponse = resp.json().await?;
-
-    if !git_repos_resp.ok {
-        // TODO: forgot to do this
-        todo!()
-    }
-
-    let git_repos = git_repos_resp.data;
     let known_repos = list_repos(state.postgres()).await?;
 
     // TODO: this is O(n^2)
@@ -106,18 +95,15 @@ pub async fn register_repository(
         .get("Authorization")
         .ok_or(ConstructumServerError::BadAuthorization)?;
     let corr_auth_tok = auth_tok.to_str()?.to_owned();
-    let resp = utils::get_with_auth(
-        format!(
-            "{}/api/v1/repos/{}/{}",
-            state.git_server_url(), payload.owner, payload.name
-        ),
-        "Authorization",
+    let git_repo: GiteaRepository = super::system::get_forge_repository(
+        state.git_forge(),
+        state.git_server_url(),
+        payload.owner.clone(),
+        payload.name.clone(),
         corr_auth_tok.clone(),
     )
     .await?;
 
-    let git_repo: GiteaRepository = resp.json().await?;
-
     // checking for existence
     match super::db::get_repo_by_git_id(git_repo.id, state.postgres()).await? {
         Some(RepoInfo {
@@ -129,6 +115,13 @@ pub async fn register_repository(
             webhook_id: _,
             enabled,
             builds_executed: _,
+            auto_cancel: _,
+            debounce_seconds: _,
+            max_concurrent_jobs: _,
+            workspace_size: _,
+            storage_class: _,
+            priority: _,
+            workspace_mode: _,
         }) if enabled => Err(ConstructumServerError::RepoAlreadyRegistered),
         Some(RepoInfo {
             repo_uuid,
@@ -139,13 +132,22 @@ pub async fn register_repository(
             webhook_id: _,
             enabled,
             builds_executed: _,
+            auto_cancel: _,
+            debounce_seconds: _,
+            max_concurrent_jobs: _,
+            workspace_size: _,
+            storage_class: _,
+            priority: _,
+            workspace_mode: _,
         }) if !enabled => {
             // just disabled
             // create wh and input
             let wh_id = super::system::add_constructum_webhook(
+                state.git_forge(),
                 state.git_server_url(),
                 git_repo.clone(),
                 corr_auth_tok.clone(),
+                state.webhook_secret(),
             )
             .await?;
 
@@ -164,9 +166,11 @@ pub async fn register_repository(
             // need to know wh_id before adding repo to DB
             // TODO: considering reordering
             let wh_id = super::system::add_constructum_webhook(
+                state.git_forge(),
                 state.git_server_url(),
                 git_repo.clone(),
                 corr_auth_tok.clone(),
+                state.webhook_secret(),
             )
             .await?;
 
@@ -181,6 +185,13 @@ pub async fn register_repository(
                 webhook_id: Some(wh_id),
                 enabled: true,
                 builds_executed: 0,
+                auto_cancel: false,
+                debounce_seconds: 0,
+                max_concurrent_jobs: None,
+                workspace_size: None,
+                storage_class: None,
+                priority: 1,
+                workspace_mode: WorkspaceMode::Volume,
             };
 
             super::db::register_repo(state.postgres(), payload).await?;
@@ -213,6 +224,7 @@ pub async fn remove_repository(
     let repo_info = super::db::get_repo(repo_id, state.postgres()).await?;
 
     super::system::remove_constructum_webhook(
+        state.git_forge(),
         state.git_server_url(),
         repo_info,
         corr_auth_tok.clone(),
@@ -226,6 +238,51 @@ pub async fn remove_repository(
     Ok((StatusCode::NO_CONTENT, ""))
 }
 
+#[tracing::instrument(skip(state))]
+pub async fn update_repo_settings(
+    Path(repo_id): Path<Uuid>,
+    State(state): State<ConstructumServerState>,
+    Json(payload): Json<RepoSettingsPayload>,
+) -> Result<Json<RepoInfo>, ConstructumServerError> {
+    // checking for existence
+    let mut repo_ref = super::db::get_repo_optional(repo_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoRepoFound)?;
+
+    // the default branch is only fetched when someone asks for pod workspaces
+    let requests_pod_workspace = payload.workspace_mode == Some(WorkspaceMode::Pod);
+    payload.apply(&mut repo_ref);
+
+    if repo_ref.debounce_seconds < 0 {
+        return Err(ConstructumServerError::InvalidRepoSettings(String::from("debounce_seconds cannot be negative")));
+    }
+
+    if repo_ref.max_concurrent_jobs.map(|x| x < 1).unwrap_or(false) {
+        return Err(ConstructumServerError::InvalidRepoSettings(String::from("max_concurrent_jobs must be at least 1")));
+    }
+
+    if repo_ref.workspace_size.as_deref().map(|x| !is_valid_workspace_size(x)).unwrap_or(false) {
+        return Err(ConstructumServerError::InvalidRepoSettings(String::from("workspace_size must be a quantity such as 10Gi")));
+    }
+
+    if repo_ref.storage_class.as_deref().map(str::is_empty).unwrap_or(false) {
+        return Err(ConstructumServerError::InvalidRepoSettings(String::from("storage_class cannot be empty")));
+    }
+
+    if !(1..=100).contains(&repo_ref.priority) {
+        return Err(ConstructumServerError::InvalidRepoSettings(String::from("priority must be between 1 and 100")));
+    }
+
+    // later pushes that add secrets are refused when their job is created
+    if requests_pod_workspace && server::read_default_pipeline(&repo_ref, &state).await?.uses_secrets() {
+        return Err(ConstructumServerError::InvalidRepoSettings(String::from(server::POD_WORKSPACE_SECRETS)));
+    }
+
+    super::db::update_repo_settings(state.postgres(), repo_id, &repo_ref).await?;
+
+    Ok(Json(repo_ref))
+}
+
 #[tracing::instrument(skip(state))]
 pub async fn jobs_for_repository(
     Path(repo_id): Path<Uuid>,
@@ -238,4 +295,31 @@ pub async fn jobs_for_repository(
 
     let results = crate::server::api::job::db::list_jobs_for_repo(repo_id, state.postgres()).await?;
     Ok(Json(results))
-}
\ No newline at end of file
+}
+
+#[tracing::instrument(skip(state))]
+pub async fn trigger_job(
+    Path(repo_id): Path<Uuid>,
+    State(state): State<ConstructumServerState>,
+    Json(payload): Json<TriggerJobPayload>,
+) -> Result<impl IntoResponse, ConstructumServerError> {
+    #[derive(Serialize)]
+    struct TriggerJobResponse {
+        job_uuid: Option<Uuid>,
+    }
+
+    let repo_info = super::db::get_repo_optional(repo_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoRepoFound)?;
+
+    let revision = match (payload.branch, payload.tag, payload.commit) {
+        (Some(branch), None, None) => JobRevision::Branch(branch),
+        (None, Some(tag), None) => JobRevision::Tag(tag),
+        (None, None, Some(commit)) => JobRevision::Commit(commit),
+        _ => return Err(ConstructumServerError::InvalidJobRequest(String::from("exactly one of branch, tag or commit must be provided"))),
+    };
+
+    let job_uuid = server::create_job_for_revision(repo_info, revision, JobTrigger::Manual, payload.parameters, state).await?;
+
+    Ok((StatusCode::CREATED, Json(TriggerJobResponse { job_uuid })))
+}
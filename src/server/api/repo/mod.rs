@@ -1,9 +1,12 @@
 pub mod db;
 pub mod endpoints;
 mod model;
-mod system;
+pub(crate) mod system;
 
-use axum::routing::{get, delete, post};
+#[cfg(test)]
+mod tests;
+
+use axum::routing::{get, delete, patch, post};
 
 use crate::ConstructumServerState;
 
@@ -13,7 +16,9 @@ pub fn register_module(router: axum::Router<ConstructumServerState, axum::body::
     router
         .route("/repos/:repo_id", get(self::endpoints::get_repo))
         .route("/repos/:repo_id", delete(self::endpoints::remove_repository))
+        .route("/repos/:repo_id/settings", patch(self::endpoints::update_repo_settings))
         .route("/repos/:repo_id/jobs", get(self::endpoints::jobs_for_repository))
+        .route("/repos/:repo_id/jobs", post(self::endpoints::trigger_job))
         .route("/repos", get(self::endpoints::list_all_repos))
         .route("/repos", post(self::endpoints::register_repository))
         .route("/known_repos", get(self::endpoints::list_known_repos))
@@ -1,4 +1,4 @@
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Deserializer};
 use sqlx::{postgres::PgRow, Row};
 use uuid::Uuid;
 
@@ -14,6 +14,18 @@ pub struct RepoInfo {
     pub webhook_id: Option<i32>,
     pub enabled: bool,
     pub builds_executed: i32,
+    // cancel older unfinished push builds of a branch when a newer push arrives
+    pub auto_cancel: bool,
+    // with auto_cancel, how long a push build waits for a newer push before it starts
+    pub debounce_seconds: i32,
+    // overrides the server-wide per-repo concurrency limit
+    pub max_concurrent_jobs: Option<i32>,
+    // overrides the server-wide workspace PVC size and storage class
+    pub workspace_size: Option<String>,
+    pub storage_class: Option<String>,
+    // weight of the repo when queued jobs of several repos compete for dispatch
+    pub priority: i32,
+    pub workspace_mode: WorkspaceMode,
 }
 
 impl<'r> sqlx::FromRow<'r, PgRow> for RepoInfo {
@@ -26,13 +38,51 @@ impl<'r> sqlx::FromRow<'r, PgRow> for RepoInfo {
         let webhook_id: Option<i32> = row.try_get("webhook_id")?;
         let enabled: bool = row.try_get("enabled")?;
         let builds_executed: i32 = row.try_get("builds_executed")?;
+        let auto_cancel: bool = row.try_get("auto_cancel")?;
+        let debounce_seconds: i32 = row.try_get("debounce_seconds")?;
+        let max_concurrent_jobs: Option<i32> = row.try_get("max_concurrent_jobs")?;
+        let workspace_size: Option<String> = row.try_get("workspace_size")?;
+        let storage_class: Option<String> = row.try_get("storage_class")?;
+        let priority: i32 = row.try_get("priority")?;
+        let workspace_mode: String = row.try_get("workspace_mode")?;
 
         Ok(
-            RepoInfo { repo_uuid: uuid, git_id, repo_url, repo_owner, repo_name, webhook_id, enabled, builds_executed }
+            RepoInfo { repo_uuid: uuid, git_id, repo_url, repo_owner, repo_name, webhook_id, enabled, builds_executed, auto_cancel, debounce_seconds, max_concurrent_jobs, workspace_size, storage_class, priority, workspace_mode: WorkspaceMode::from(workspace_mode) }
         )
     }
 }
 
+// where the steps of a repo's jobs share their workspace
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorkspaceMode {
+    // a ReadWriteMany PVC that every step Job mounts
+    #[default]
+    Volume,
+    // an emptyDir of the client pod, with the steps as ephemeral containers of that pod.
+    // needs no RWX storage, but failed jobs cannot be resumed and steps cannot use vault secrets,
+    // so repos whose pipeline declares secrets cannot switch to it and such jobs are refused
+    Pod,
+}
+
+impl From<WorkspaceMode> for &'static str {
+    fn from(value: WorkspaceMode) -> Self {
+        match value {
+            WorkspaceMode::Volume => "Volume",
+            WorkspaceMode::Pod => "Pod",
+        }
+    }
+}
+
+impl From<String> for WorkspaceMode {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "Volume" => WorkspaceMode::Volume,
+            "Pod" => WorkspaceMode::Pod,
+            _ => panic!("invalid WorkspaceMode")
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GiteaRepository {
     pub id: i32,
@@ -49,12 +99,102 @@ pub struct GiteaUser {
     pub login: String
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GitlabProject {
+    pub id: i32,
+    pub name: String,
+    // the URL-safe name, which is what repositories are registered under
+    pub path: String,
+    pub description: Option<String>,
+    pub web_url: String,
+    pub ssh_url_to_repo: String,
+    pub http_url_to_repo: String,
+    pub namespace: GitlabNamespace,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GitlabNamespace {
+    pub id: i32,
+    pub path: String,
+    pub full_path: String,
+}
+
+// the rest of the server (and the UI) only knows about Gitea-shaped repositories,
+// so GitLab projects are folded into that shape as soon as they are fetched.
+impl From<GitlabProject> for GiteaRepository {
+    fn from(value: GitlabProject) -> Self {
+        GiteaRepository {
+            id: value.id,
+            name: value.path,
+            description: value.description.unwrap_or_default(),
+            html_url: value.web_url,
+            ssh_url: value.ssh_url_to_repo,
+            owner: GiteaUser { id: value.namespace.id, login: value.namespace.full_path },
+        }
+    }
+}
+
+// splits `owner/name` at the last slash, since a GitLab owner can be a nested group like `group/subgroup`
+pub fn split_repo_path(path: &str) -> Option<(&str, &str)> {
+    path.rsplit_once('/').filter(|(owner, name)| !owner.is_empty() && !name.is_empty())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RegisterRepositoryPayload {
     pub owner: String,
     pub name: String,
 }
 
+// a partial update: settings left out keep their current value, the overrides are cleared with an explicit null
+#[derive(Debug, Deserialize)]
+pub struct RepoSettingsPayload {
+    pub auto_cancel: Option<bool>,
+    pub debounce_seconds: Option<i32>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub max_concurrent_jobs: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub workspace_size: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub storage_class: Option<Option<String>>,
+    pub priority: Option<i32>,
+    pub workspace_mode: Option<WorkspaceMode>,
+}
+
+impl RepoSettingsPayload {
+    pub fn apply(self, repo: &mut RepoInfo) {
+        if let Some(auto_cancel) = self.auto_cancel {
+            repo.auto_cancel = auto_cancel;
+        }
+        if let Some(debounce_seconds) = self.debounce_seconds {
+            repo.debounce_seconds = debounce_seconds;
+        }
+        if let Some(max_concurrent_jobs) = self.max_concurrent_jobs {
+            repo.max_concurrent_jobs = max_concurrent_jobs;
+        }
+        if let Some(workspace_size) = self.workspace_size {
+            repo.workspace_size = workspace_size;
+        }
+        if let Some(storage_class) = self.storage_class {
+            repo.storage_class = storage_class;
+        }
+        if let Some(priority) = self.priority {
+            repo.priority = priority;
+        }
+        if let Some(workspace_mode) = self.workspace_mode {
+            repo.workspace_mode = workspace_mode;
+        }
+    }
+}
+
+// tells a null apart from a missing field, which serde(default) leaves at None
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GitRepoResponse {
     pub id: Option<Uuid>,
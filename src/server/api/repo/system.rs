@@ -1,13 +1,82 @@
-use reqwest::StatusCode;
+use reqwest::{header::HeaderMap, StatusCode};
 use serde::{Serialize, Deserialize};
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::{server::error::ConstructumServerError, utils::{post_with_auth, delete_with_auth}};
+use crate::{config::GitForge, pipeline::PipelineStatus, server::error::ConstructumServerError, utils::{get_with_auth, post_with_auth, delete_with_auth}};
 
-use super::{GiteaRepository, RepoInfo};
+use super::{GiteaRepository, GitlabProject, RepoInfo};
+
+// TODO: configurable
+const CONSTRUCTUM_WEBHOOK_URL: &str = "http://10.16.24.2:3001/api/v1/webhook";
 
 #[tracing::instrument(skip(token))]
-pub async fn add_constructum_webhook(url: String, repo: GiteaRepository, token: String) -> Result<i32, ConstructumServerError> {
+pub async fn list_forge_repositories(forge: GitForge, url: String, token: String) -> Result<Vec<GiteaRepository>, ConstructumServerError> {
+    match forge {
+        GitForge::Gitea => {
+            let resp = get_with_auth(format!("{url}/api/v1/repos/search"), "Authorization", token).await?;
+
+            #[derive(Debug, Deserialize)]
+            struct GiteaRepositoryResponse {
+                data: Vec<GiteaRepository>,
+                ok: bool,
+            }
+
+            let git_repos_resp: GiteaRepositoryResponse = resp.json().await?;
+
+            if !git_repos_resp.ok {
+                return Err(ConstructumServerError::ForgeRequestFailed(String::from("Gitea could not search repositories")));
+            }
+
+            Ok(git_repos_resp.data)
+        },
+        GitForge::Gitlab => {
+            let mut repositories = Vec::new();
+            let mut page = Some(1);
+            while let Some(current) = page {
+                let resp = get_with_auth(format!("{url}/api/v4/projects?membership=true&per_page=100&page={current}"), "Authorization", token.clone()).await?;
+                page = next_gitlab_page(resp.headers());
+                let projects: Vec<GitlabProject> = resp.json().await?;
+                repositories.extend(projects.into_iter().map(GiteaRepository::from));
+            }
+
+            Ok(repositories)
+        },
+    }
+}
+
+// GitLab leaves X-Next-Page empty on the last page
+pub(super) fn next_gitlab_page(headers: &HeaderMap) -> Option<u32> {
+    headers.get("x-next-page")
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.trim().parse().ok())
+}
+
+#[tracing::instrument(skip(token))]
+pub async fn get_forge_repository(forge: GitForge, url: String, owner: String, name: String, token: String) -> Result<GiteaRepository, ConstructumServerError> {
+    match forge {
+        GitForge::Gitea => {
+            let resp = get_with_auth(format!("{url}/api/v1/repos/{owner}/{name}"), "Authorization", token).await?;
+            Ok(resp.json().await?)
+        },
+        GitForge::Gitlab => {
+            // GitLab addresses projects by their URL-encoded full path
+            let project_path = format!("{owner}/{name}").replace('/', "%2F");
+            let resp = get_with_auth(format!("{url}/api/v4/projects/{project_path}"), "Authorization", token).await?;
+            let project: GitlabProject = resp.json().await?;
+            Ok(project.into())
+        },
+    }
+}
+
+#[tracing::instrument(skip(token, secret))]
+pub async fn add_constructum_webhook(forge: GitForge, url: String, repo: GiteaRepository, token: String, secret: Option<String>) -> Result<i32, ConstructumServerError> {
+    match forge {
+        GitForge::Gitea => add_gitea_webhook(url, repo, token).await,
+        GitForge::Gitlab => add_gitlab_webhook(url, repo, token, secret).await,
+    }
+}
+
+async fn add_gitea_webhook(url: String, repo: GiteaRepository, token: String) -> Result<i32, ConstructumServerError> {
 
     #[derive(Debug, Serialize)]
     struct CreateWebhookConfig {
@@ -31,7 +100,7 @@ pub async fn add_constructum_webhook(url: String, repo: GiteaRepository, token:
         active: true,
         // TODO: configurable
         branch_filter: None,
-        config: CreateWebhookConfig { content_type: "json".to_owned(), url: "http://10.16.24.2:3001/api/v1/webhook".to_owned() },
+        config: CreateWebhookConfig { content_type: "json".to_owned(), url: CONSTRUCTUM_WEBHOOK_URL.to_owned() },
         // TODO: configurable
         events: vec!["push".to_owned()],
         wh_type: "gitea".to_owned(),
@@ -52,16 +121,54 @@ pub async fn add_constructum_webhook(url: String, repo: GiteaRepository, token:
     Ok(resp_id.id)
 }
 
-#[tracing::instrument]
-pub async fn remove_constructum_webhook(url: String, db_repo: RepoInfo, token: String) -> Result<(), ConstructumServerError> {
+async fn add_gitlab_webhook(url: String, repo: GiteaRepository, token: String, secret: Option<String>) -> Result<i32, ConstructumServerError> {
+
+    #[derive(Debug, Serialize)]
+    struct CreateProjectHookPayload {
+        url: String,
+        push_events: bool,
+        tag_push_events: bool,
+        merge_requests_events: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    }
+
+    let cphp = CreateProjectHookPayload {
+        url: CONSTRUCTUM_WEBHOOK_URL.to_owned(),
+        push_events: true,
+        tag_push_events: true,
+        merge_requests_events: true,
+        token: secret,
+    };
+
+    let body = serde_json::to_string(&cphp)?;
+    let req_url = format!("{url}/api/v4/projects/{}/hooks", repo.id);
+    let resp = post_with_auth(req_url, "Authorization", token, body, "application/json").await?;
+
+    #[derive(Debug, Deserialize)]
+    struct CreateProjectHookResponse {
+        id: i32
+    }
+
+    let resp_id: CreateProjectHookResponse = resp.json().await?;
+
+    Ok(resp_id.id)
+}
+
+#[tracing::instrument(skip(token))]
+pub async fn remove_constructum_webhook(forge: GitForge, url: String, db_repo: RepoInfo, token: String) -> Result<(), ConstructumServerError> {
     match db_repo.webhook_id {
         Some(wh_id) => {
-            let resp = delete_with_auth(format!("{url}/api/v1/repos/{}/{}/hooks/{}", db_repo.repo_owner, db_repo.repo_name, wh_id), "Authorization", token).await?;
+            let req_url = match forge {
+                GitForge::Gitea => format!("{url}/api/v1/repos/{}/{}/hooks/{}", db_repo.repo_owner, db_repo.repo_name, wh_id),
+                GitForge::Gitlab => format!("{url}/api/v4/projects/{}/hooks/{}", db_repo.git_id, wh_id),
+            };
+            let resp = delete_with_auth(req_url, "Authorization", token).await?;
 
-            if resp.status() != StatusCode::OK {
+            if !resp.status().is_success() {
                 // TODO: return error
             }
-        
+
             Ok(())
         },
         None => {
@@ -69,4 +176,56 @@ pub async fn remove_constructum_webhook(url: String, db_repo: RepoInfo, token: S
         }
     }
 
-}
\ No newline at end of file
+}
+
+#[tracing::instrument(skip(token))]
+pub async fn report_commit_status(forge: GitForge, url: String, db_repo: &RepoInfo, commit_id: &str, status: PipelineStatus, token: String) -> Result<(), ConstructumServerError> {
+    let (req_url, header_name, token, body) = match forge {
+        GitForge::Gitea => {
+            #[derive(Debug, Serialize)]
+            struct GiteaCommitStatus {
+                state: &'static str,
+                context: &'static str,
+                description: &'static str,
+            }
+
+            let state = match status {
+                PipelineStatus::Queued | PipelineStatus::InProgress => "pending",
+                PipelineStatus::Complete => "success",
+                PipelineStatus::Failed | PipelineStatus::TimedOut => "failure",
+                // gitea has no cancelled state; warning keeps it from reading as a broken commit
+                PipelineStatus::Cancelled => "warning",
+            };
+
+            let body = serde_json::to_string(&GiteaCommitStatus { state, context: "constructum", description: status.into() })?;
+            (format!("{url}/api/v1/repos/{}/{}/statuses/{commit_id}", db_repo.repo_owner, db_repo.repo_name), "Authorization", format!("token {token}"), body)
+        },
+        GitForge::Gitlab => {
+            #[derive(Debug, Serialize)]
+            struct GitlabCommitStatus {
+                state: &'static str,
+                name: &'static str,
+                description: &'static str,
+            }
+
+            let state = match status {
+                PipelineStatus::Queued => "pending",
+                PipelineStatus::InProgress => "running",
+                PipelineStatus::Complete => "success",
+                PipelineStatus::Failed | PipelineStatus::TimedOut => "failed",
+                PipelineStatus::Cancelled => "canceled",
+            };
+
+            let body = serde_json::to_string(&GitlabCommitStatus { state, name: "constructum", description: status.into() })?;
+            (format!("{url}/api/v4/projects/{}/statuses/{commit_id}", db_repo.git_id), "PRIVATE-TOKEN", token, body)
+        },
+    };
+
+    let resp = post_with_auth(req_url, header_name, token, body, "application/json").await?;
+
+    if resp.status() != StatusCode::OK && resp.status() != StatusCode::CREATED {
+        warn!("forge rejected commit status for {commit_id}: {}", resp.status());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use reqwest::header::{HeaderMap, HeaderValue};
+use uuid::Uuid;
+
+use super::{system::next_gitlab_page, split_repo_path, RepoInfo, RepoSettingsPayload, WorkspaceMode};
+
+fn repo_info() -> RepoInfo {
+    RepoInfo {
+        repo_uuid: Uuid::new_v4(),
+        git_id: 1,
+        repo_url: String::from("https://git.example.com/owner/repo.git"),
+        repo_owner: String::from("owner"),
+        repo_name: String::from("repo"),
+        webhook_id: None,
+        enabled: true,
+        builds_executed: 0,
+        auto_cancel: false,
+        debounce_seconds: 0,
+        max_concurrent_jobs: Some(3),
+        workspace_size: Some(String::from("10Gi")),
+        storage_class: None,
+        priority: 50,
+        workspace_mode: WorkspaceMode::Pod,
+    }
+}
+
+#[test]
+fn test_settings_update_keeps_fields_left_out() {
+    let mut repo = repo_info();
+    let payload: RepoSettingsPayload = serde_json::from_str(r#"{ "auto_cancel": true, "workspace_size": null }"#).expect("failed to parse settings");
+
+    payload.apply(&mut repo);
+
+    assert!(repo.auto_cancel);
+    assert_eq!(None, repo.workspace_size);
+    assert_eq!(Some(3), repo.max_concurrent_jobs);
+    assert_eq!(50, repo.priority);
+    assert_eq!(WorkspaceMode::Pod, repo.workspace_mode);
+}
+
+#[test]
+fn test_split_repo_path_keeps_nested_namespaces_in_the_owner() {
+    assert_eq!(split_repo_path("owner/repo"), Some(("owner", "repo")));
+    assert_eq!(split_repo_path("group/subgroup/repo"), Some(("group/subgroup", "repo")));
+    assert_eq!(split_repo_path("repo"), None);
+    assert_eq!(split_repo_path("owner/"), None);
+}
+
+#[test]
+fn test_next_gitlab_page() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(next_gitlab_page(&headers), None);
+
+    headers.insert("X-Next-Page", HeaderValue::from_static("2"));
+    assert_eq!(next_gitlab_page(&headers), Some(2));
+
+    headers.insert("X-Next-Page", HeaderValue::from_static(""));
+    assert_eq!(next_gitlab_page(&headers), None);
+}
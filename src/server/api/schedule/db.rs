@@ -0,0 +1,117 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, types::Json};
+use uuid::Uuid;
+
+use super::{ScheduleInfo, SchedulePayload};
+
+pub async fn list_schedules_for_repo(
+    repo_id: Uuid,
+    pool: PgPool
+) -> Result<Vec<ScheduleInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.schedules WHERE repo_id = $1")
+        .bind(repo_id)
+        .fetch_all(&mut sql_connection)
+        .await
+}
+
+pub async fn get_schedule(
+    repo_id: Uuid,
+    schedule_id: Uuid,
+    pool: PgPool
+) -> Result<Option<ScheduleInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.schedules WHERE repo_id = $1 AND id = $2")
+        .bind(repo_id)
+        .bind(schedule_id)
+        .fetch_optional(&mut sql_connection)
+        .await
+}
+
+pub async fn list_due_schedules(
+    pool: PgPool
+) -> Result<Vec<ScheduleInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.schedules WHERE enabled = TRUE AND next_run <= now()")
+        .fetch_all(&mut sql_connection)
+        .await
+}
+
+pub async fn create_schedule(
+    pool: PgPool,
+    schedule_id: Uuid,
+    repo_id: Uuid,
+    payload: SchedulePayload,
+    next_run: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query("INSERT INTO constructum.schedules (id, repo_id, cron_expression, branch, parameters, enabled, next_run) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+        .bind(schedule_id)
+        .bind(repo_id)
+        .bind(payload.cron_expression)
+        .bind(payload.branch)
+        .bind(Json(payload.parameters))
+        .bind(payload.enabled)
+        .bind(next_run)
+        .execute(&mut sql_connection).await?;
+    Ok(())
+}
+
+pub async fn update_schedule(
+    pool: PgPool,
+    schedule_id: Uuid,
+    payload: SchedulePayload,
+    next_run: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query("UPDATE constructum.schedules SET cron_expression = $2, branch = $3, parameters = $4, enabled = $5, next_run = $6 WHERE id = $1")
+        .bind(schedule_id)
+        .bind(payload.cron_expression)
+        .bind(payload.branch)
+        .bind(Json(payload.parameters))
+        .bind(payload.enabled)
+        .bind(next_run)
+        .execute(&mut sql_connection).await?;
+    Ok(())
+}
+
+pub async fn delete_schedule(
+    pool: PgPool,
+    schedule_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query("DELETE FROM constructum.schedules WHERE id = $1")
+        .bind(schedule_id)
+        .execute(&mut sql_connection).await?;
+    Ok(())
+}
+
+// moves the schedule to its next run, but only if nobody else has claimed this run already.
+// returns whether this caller won the claim and should start the job.
+pub async fn claim_schedule_run(
+    pool: PgPool,
+    schedule_id: Uuid,
+    claimed_run: DateTime<Utc>,
+    next_run: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    let result = sqlx::query("UPDATE constructum.schedules SET next_run = $3, last_run = now() WHERE id = $1 AND next_run = $2")
+        .bind(schedule_id)
+        .bind(claimed_run)
+        .bind(next_run)
+        .execute(&mut sql_connection).await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn record_schedule_job(
+    pool: PgPool,
+    schedule_id: Uuid,
+    job_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query("UPDATE constructum.schedules SET last_job = $2 WHERE id = $1")
+        .bind(schedule_id)
+        .bind(job_id)
+        .execute(&mut sql_connection).await?;
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{server::{self, api::repo::RepoInfo, error::ConstructumServerError}, ConstructumServerState};
+
+use super::{ScheduleInfo, SchedulePayload};
+
+pub async fn list_schedules(
+    Path(repo_id): Path<Uuid>,
+    State(state): State<ConstructumServerState>,
+) -> Result<Json<Vec<ScheduleInfo>>, ConstructumServerError> {
+    // checking for existence
+    let _repo_ref = crate::server::api::repo::db::get_repo_optional(repo_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoRepoFound)?;
+
+    let schedules = super::db::list_schedules_for_repo(repo_id, state.postgres()).await?;
+
+    Ok(Json(schedules))
+}
+
+pub async fn get_schedule(
+    Path((repo_id, schedule_id)): Path<(Uuid, Uuid)>,
+    State(state): State<ConstructumServerState>,
+) -> Result<Json<ScheduleInfo>, ConstructumServerError> {
+    let schedule = super::db::get_schedule(repo_id, schedule_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoScheduleFound)?;
+
+    Ok(Json(schedule))
+}
+
+#[tracing::instrument(skip(state))]
+pub async fn create_schedule(
+    Path(repo_id): Path<Uuid>,
+    State(state): State<ConstructumServerState>,
+    Json(payload): Json<SchedulePayload>,
+) -> Result<impl IntoResponse, ConstructumServerError> {
+    #[derive(Serialize)]
+    struct CreateScheduleResponse {
+        uuid: Uuid,
+    }
+
+    let repo_ref = crate::server::api::repo::db::get_repo_optional(repo_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoRepoFound)?;
+
+    let next_run = first_run(&payload.cron_expression)?;
+    check_parameters(&repo_ref, &payload, &state).await?;
+    let schedule_id = Uuid::new_v4();
+    super::db::create_schedule(state.postgres(), schedule_id, repo_id, payload, next_run).await?;
+
+    Ok((StatusCode::CREATED, Json(CreateScheduleResponse { uuid: schedule_id })))
+}
+
+#[tracing::instrument(skip(state))]
+pub async fn update_schedule(
+    Path((repo_id, schedule_id)): Path<(Uuid, Uuid)>,
+    State(state): State<ConstructumServerState>,
+    Json(payload): Json<SchedulePayload>,
+) -> Result<Json<ScheduleInfo>, ConstructumServerError> {
+    // checking for existence
+    let _schedule = super::db::get_schedule(repo_id, schedule_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoScheduleFound)?;
+    let repo_ref = crate::server::api::repo::db::get_repo_optional(repo_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoRepoFound)?;
+
+    let next_run = first_run(&payload.cron_expression)?;
+    check_parameters(&repo_ref, &payload, &state).await?;
+    super::db::update_schedule(state.postgres(), schedule_id, payload, next_run).await?;
+
+    let schedule = super::db::get_schedule(repo_id, schedule_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoScheduleFound)?;
+
+    Ok(Json(schedule))
+}
+
+pub async fn delete_schedule(
+    Path((repo_id, schedule_id)): Path<(Uuid, Uuid)>,
+    State(state): State<ConstructumServerState>,
+) -> Result<impl IntoResponse, ConstructumServerError> {
+    // checking for existence
+    let _schedule = super::db::get_schedule(repo_id, schedule_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoScheduleFound)?;
+
+    super::db::delete_schedule(state.postgres(), schedule_id).await?;
+
+    Ok((StatusCode::NO_CONTENT, ""))
+}
+
+fn first_run(cron_expression: &str) -> Result<chrono::DateTime<Utc>, ConstructumServerError> {
+    let schedule = super::parse_cron_expression(cron_expression)
+        .map_err(|e| ConstructumServerError::InvalidSchedule(format!("{e}")))?;
+
+    super::next_run_after(&schedule, Utc::now())
+        .ok_or_else(|| ConstructumServerError::InvalidSchedule(String::from("cron expression never fires")))
+}
+
+// a schedule with parameters its pipeline would reject should fail now, not every time it fires
+async fn check_parameters(repo: &RepoInfo, payload: &SchedulePayload, state: &ConstructumServerState) -> Result<(), ConstructumServerError> {
+    let pipeline = server::read_branch_pipeline(repo, payload.branch.clone(), state).await?;
+    pipeline.resolve_parameters(&payload.parameters, true)?;
+
+    Ok(())
+}
@@ -0,0 +1,21 @@
+pub mod db;
+pub mod endpoints;
+mod model;
+
+#[cfg(test)]
+mod tests;
+
+use axum::routing::{get, post, put, delete};
+
+use crate::ConstructumServerState;
+
+pub use self::model::*;
+
+pub fn register_module(router: axum::Router<ConstructumServerState, axum::body::Body>) -> axum::Router<ConstructumServerState, axum::body::Body> {
+    router
+        .route("/repos/:repo_id/schedules", get(self::endpoints::list_schedules))
+        .route("/repos/:repo_id/schedules", post(self::endpoints::create_schedule))
+        .route("/repos/:repo_id/schedules/:schedule_id", get(self::endpoints::get_schedule))
+        .route("/repos/:repo_id/schedules/:schedule_id", put(self::endpoints::update_schedule))
+        .route("/repos/:repo_id/schedules/:schedule_id", delete(self::endpoints::delete_schedule))
+}
@@ -0,0 +1,97 @@
+use std::{collections::HashMap, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use sqlx::{postgres::PgRow, Row, types::Json};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct ScheduleInfo {
+    pub schedule_uuid: Uuid,
+    pub repo_id: Uuid,
+    pub cron_expression: String,
+    pub branch: String,
+    pub parameters: HashMap<String, serde_json::Value>,
+    pub enabled: bool,
+    pub next_run: DateTime<Utc>,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_job: Option<Uuid>,
+}
+
+impl<'r> sqlx::FromRow<'r, PgRow> for ScheduleInfo {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let uuid: Uuid = row.try_get("id")?;
+        let repo_id: Uuid = row.try_get("repo_id")?;
+        let cron_expression: String = row.try_get("cron_expression")?;
+        let branch: String = row.try_get("branch")?;
+        let parameters: Json<HashMap<String, serde_json::Value>> = row.try_get("parameters")?;
+        let enabled: bool = row.try_get("enabled")?;
+        let next_run: DateTime<Utc> = row.try_get("next_run")?;
+        let last_run: Option<DateTime<Utc>> = row.try_get("last_run")?;
+        let last_job: Option<Uuid> = row.try_get("last_job")?;
+
+        Ok(
+            ScheduleInfo { schedule_uuid: uuid, repo_id, cron_expression, branch, parameters: parameters.0, enabled, next_run, last_run, last_job }
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SchedulePayload {
+    pub cron_expression: String,
+    pub branch: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+const DAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+// accepts both the classic five field form and the six/seven field form with seconds (and years)
+pub fn parse_cron_expression(expression: &str) -> Result<cron::Schedule, cron::error::Error> {
+    let expression = expression.trim();
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    match fields.len() {
+        5 => {
+            // the cron crate counts days of the week from SUN=1, crontab from SUN=0 (or 7)
+            let day_of_week = crontab_day_of_week(fields[4]);
+            cron::Schedule::from_str(&format!("0 {} {day_of_week}", fields[..4].join(" ")))
+        }
+        _ => cron::Schedule::from_str(expression),
+    }
+}
+
+// spells out numeric crontab days of the week as names, anything else is left for the cron crate to judge
+fn crontab_day_of_week(field: &str) -> String {
+    field.split(',').map(|item| crontab_day_of_week_item(item).unwrap_or_else(|| item.to_string())).collect::<Vec<_>>().join(",")
+}
+
+fn crontab_day_of_week_item(item: &str) -> Option<String> {
+    let (range, step) = match item.split_once('/') {
+        Some((range, step)) => (range, Some(step.parse::<usize>().ok().filter(|x| *x > 0)?)),
+        None => (item, None),
+    };
+
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (start.parse::<usize>().ok()?, end.parse::<usize>().ok()?),
+        None => {
+            let start = range.parse::<usize>().ok()?;
+            (start, if step.is_some() { 7 } else { start })
+        }
+    };
+    if start > end || end > 7 {
+        return None;
+    }
+
+    let days: Vec<&str> = (start..=end).step_by(step.unwrap_or(1)).map(|x| DAY_NAMES[x % 7]).collect();
+    Some(days.join(","))
+}
+
+pub fn next_run_after(schedule: &cron::Schedule, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    schedule.after(&after).next()
+}
@@ -0,0 +1,58 @@
+use chrono::{Datelike, TimeZone, Utc, Weekday};
+
+use super::{parse_cron_expression, next_run_after};
+
+#[test]
+fn test_parse_five_field_cron_expression() {
+    let schedule = parse_cron_expression("30 2 * * *").expect("failed to parse cron expression");
+    let start = Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+
+    let next = next_run_after(&schedule, start).expect("no next run");
+
+    assert_eq!(next, Utc.with_ymd_and_hms(2023, 5, 2, 2, 30, 0).unwrap());
+}
+
+#[test]
+fn test_parse_invalid_cron_expression() {
+    assert!(parse_cron_expression("every night").is_err());
+}
+
+#[test]
+fn test_crontab_weekday_range() {
+    let schedule = parse_cron_expression("* * * * 1-5").expect("failed to parse cron expression");
+    // a saturday
+    let start = Utc.with_ymd_and_hms(2023, 5, 6, 12, 0, 0).unwrap();
+
+    let next = next_run_after(&schedule, start).expect("no next run");
+    assert_eq!(next, Utc.with_ymd_and_hms(2023, 5, 8, 0, 0, 0).unwrap());
+
+    let weekdays: Vec<Weekday> = schedule.after(&start).take(7 * 24 * 60).map(|x| x.weekday()).collect();
+    assert!(!weekdays.contains(&Weekday::Sat));
+    assert!(!weekdays.contains(&Weekday::Sun));
+    assert!(weekdays.contains(&Weekday::Mon));
+    assert!(weekdays.contains(&Weekday::Fri));
+}
+
+#[test]
+fn test_crontab_sunday_as_zero() {
+    let schedule = parse_cron_expression("0 3 * * 0").expect("failed to parse cron expression");
+    // a monday
+    let start = Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+
+    let next = next_run_after(&schedule, start).expect("no next run");
+    assert_eq!(next, Utc.with_ymd_and_hms(2023, 5, 7, 3, 0, 0).unwrap());
+    assert_eq!(next.weekday(), Weekday::Sun);
+}
+
+#[test]
+fn test_crontab_sunday_as_seven_and_names() {
+    let start = Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+
+    let seven = parse_cron_expression("0 3 * * 5-7").expect("failed to parse cron expression");
+    let names = parse_cron_expression("0 3 * * fri,sat,sun").expect("failed to parse cron expression");
+
+    let seven: Vec<_> = seven.after(&start).take(3).collect();
+    let names: Vec<_> = names.after(&start).take(3).collect();
+    assert_eq!(seven, names);
+    assert_eq!(seven[2].weekday(), Weekday::Sun);
+}
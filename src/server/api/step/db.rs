@@ -1,9 +1,9 @@
-use sqlx::{PgPool, FromRow};
+use sqlx::{PgPool, FromRow, types::Json};
 use uuid::Uuid;
 
 use crate::pipeline::PipelineStep;
 
-use super::model::{CompletedPipelineStep, StepStatus};
+use super::model::{CompletedPipelineStep, StepAttempt, StepStatus};
 
 pub async fn list_steps(
     pool: PgPool
@@ -85,6 +85,58 @@ pub async fn update_step_status(
     Ok(())
 }
 
+pub async fn update_step_failure_reason(
+    pool: PgPool,
+    id: Uuid,
+    reason: String,
+) -> Result<(), sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query("UPDATE constructum.steps SET failure_reason = $2 WHERE id = $1")
+        .bind(id)
+        .bind(reason)
+        .execute(&mut sql_connection).await?;
+    Ok(())
+}
+
+pub async fn update_step_image_digests(
+    pool: PgPool,
+    id: Uuid,
+    images: Vec<String>,
+) -> Result<(), sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query("UPDATE constructum.steps SET image_digests = $2 WHERE id = $1")
+        .bind(id)
+        .bind(images)
+        .execute(&mut sql_connection).await?;
+    Ok(())
+}
+
+pub async fn add_interrupted_attempt(
+    pool: PgPool,
+    id: Uuid,
+    attempt: StepAttempt,
+) -> Result<(), sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query("UPDATE constructum.steps SET interrupted_attempts = interrupted_attempts || $2 WHERE id = $1")
+        .bind(id)
+        .bind(Json(vec![attempt]))
+        .execute(&mut sql_connection).await?;
+    Ok(())
+}
+
+pub async fn finish_unfinished_steps(
+    pool: PgPool,
+    job_id: Uuid,
+    status: StepStatus,
+) -> Result<(), sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query("UPDATE constructum.steps SET status = $2 WHERE job = $1 AND status IN ('NotStarted', 'InProgress')")
+        .bind(job_id)
+        .bind(Into::<&str>::into(status))
+        .execute(&mut sql_connection).await?;
+    Ok(())
+}
+
 pub async fn update_step_logs(
     pool: PgPool,
     id: Uuid,
@@ -1,23 +1,26 @@
-use axum::{extract::State, Json, http::StatusCode, response::IntoResponse};
+use axum::{extract::{Query, State}, Json, http::StatusCode, response::IntoResponse};
 use futures::future::join_all;
 use uuid::Uuid;
 
 use crate::{server::error::ConstructumServerError, ConstructumServerState};
 
-use super::model::{StepLogs, CompletedPipelineStep};
+use super::model::{StepLogs, CompletedPipelineStep, LogRangeQuery};
 
 pub async fn get_log_for_step(
     State(state): State<ConstructumServerState>,
     axum::extract::Path((job_id, step_id)): axum::extract::Path<(Uuid, Uuid)>,
+    Query(range): Query<LogRangeQuery>,
 ) -> Result<impl IntoResponse, ConstructumServerError> {
     
     let step: CompletedPipelineStep = super::db::get_step(state.postgres(), step_id).await?;
     let job = super::super::job::db::get_job(job_id, state.postgres()).await?;
 
-    let log = crate::redis::grab_log_from_redis(state.redis(), format!("pipeline-{}-{}", job.job_uuid, step.name.clone()), step.name).await?;
+    let incremental = range.after.is_some();
+    let log = crate::redis::grab_log_from_redis(state.redis(), job.job_uuid, step.id.to_string(), range.after).await?;
 
     Ok(match log {
-        Some(log_str) => (StatusCode::OK, Json(StepLogs::Logs(log_str))),
+        Some((log, last_id)) if incremental => (StatusCode::OK, Json(StepLogs::Range { log, last_id })),
+        Some((log_str, _)) => (StatusCode::OK, Json(StepLogs::Logs(log_str))),
         None => {
             // TODO: this should only return when it matches job and step
             let log_keys = super::db::get_logs_for_step(state.postgres(), step_id).await?;
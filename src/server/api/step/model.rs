@@ -1,5 +1,6 @@
+use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
-use sqlx::{FromRow, postgres::PgRow, Row};
+use sqlx::{FromRow, postgres::PgRow, Row, types::Json};
 use uuid::Uuid;
 
 #[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
@@ -11,6 +12,19 @@ pub struct CompletedPipelineStep {
     pub commands: Vec<String>,
     pub status: StepStatus,
     pub log_key: Option<Vec<String>>,
+    pub failure_reason: Option<String>,
+    // name@digest of every image the step pushed
+    pub image_digests: Option<Vec<String>>,
+    pub interrupted_attempts: Vec<StepAttempt>,
+}
+
+// a run of a step that the cluster took away before it finished, and that was retried
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct StepAttempt {
+    pub attempt: u32,
+    pub reason: String,
+    pub log_keys: Vec<String>,
+    pub finished_at: DateTime<Utc>,
 }
 
 impl<'r> FromRow<'r, PgRow> for CompletedPipelineStep {
@@ -23,8 +37,11 @@ impl<'r> FromRow<'r, PgRow> for CompletedPipelineStep {
         let commands: Vec<String> = row.try_get("commands")?;
         let status = StepStatus::from_row(row)?;
         let log_keys: Option<Vec<String>> = row.try_get("log_keys")?;
+        let failure_reason: Option<String> = row.try_get("failure_reason")?;
+        let image_digests: Option<Vec<String>> = row.try_get("image_digests")?;
+        let interrupted_attempts: Json<Vec<StepAttempt>> = row.try_get("interrupted_attempts")?;
         Ok(
-            CompletedPipelineStep { id, name, step_number: step_num, image, commands, status, log_key: log_keys }
+            CompletedPipelineStep { id, name, step_number: step_num, image, commands, status, log_key: log_keys, failure_reason, image_digests, interrupted_attempts: interrupted_attempts.0 }
         )
     }
 }
@@ -34,16 +51,20 @@ pub enum StepStatus {
     NotStarted,
     InProgress,
     Success,
-    Fail
+    Fail,
+    Skipped,
+    Cancelled,
 }
 
-impl<'a> From<StepStatus> for &'a str {
+impl From<StepStatus> for &'static str {
     fn from(value: StepStatus) -> Self {
         match value {
             StepStatus::NotStarted => "NotStarted",
             StepStatus::InProgress => "InProgress",
             StepStatus::Success => "Success",
             StepStatus::Fail => "Fail",
+            StepStatus::Skipped => "Skipped",
+            StepStatus::Cancelled => "Cancelled",
         }    
     }
 }
@@ -55,6 +76,8 @@ impl From<String> for StepStatus {
             "InProgress" => StepStatus::InProgress,
             "Success" => StepStatus::Success,
             "Fail" => StepStatus::Fail,
+            "Skipped" => StepStatus::Skipped,
+            "Cancelled" => StepStatus::Cancelled,
             _ => panic!("bad stepstatus")
         }
     }
@@ -72,4 +95,12 @@ pub enum StepLogs {
     None,
     Logs(String),
     ManyLogs(Vec<String>),
+    // the live log following the entry a caller already has, and the id to ask after next time
+    Range { log: String, last_id: Option<String> },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogRangeQuery {
+    // id of the last live log entry the caller has seen, or 0 to read from the start
+    pub after: Option<String>,
 }
\ No newline at end of file
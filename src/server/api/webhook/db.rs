@@ -0,0 +1,92 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::DeliveryInfo;
+
+pub async fn list_deliveries_for_repo(
+    repo_id: Uuid,
+    pool: PgPool
+) -> Result<Vec<DeliveryInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.webhook_deliveries WHERE repo_id = $1 ORDER BY received_at DESC")
+        .bind(repo_id)
+        .fetch_all(&mut sql_connection)
+        .await
+}
+
+pub async fn get_delivery(
+    repo_id: Uuid,
+    id: Uuid,
+    pool: PgPool
+) -> Result<Option<DeliveryInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.webhook_deliveries WHERE repo_id = $1 AND id = $2")
+        .bind(repo_id)
+        .bind(id)
+        .fetch_optional(&mut sql_connection)
+        .await
+}
+
+pub async fn get_delivery_by_delivery_id(
+    delivery_id: &str,
+    pool: PgPool
+) -> Result<Option<DeliveryInfo>, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query_as("SELECT * FROM constructum.webhook_deliveries WHERE delivery_id = $1")
+        .bind(delivery_id)
+        .fetch_optional(&mut sql_connection)
+        .await
+}
+
+// returns false if a delivery with the same delivery ID was recorded first
+pub async fn record_delivery(
+    pool: PgPool,
+    id: Uuid,
+    delivery_id: &str,
+    event_type: &str,
+    payload: &str,
+    redelivery_of: Option<Uuid>,
+) -> Result<bool, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    let result = sqlx::query("INSERT INTO constructum.webhook_deliveries (id, delivery_id, event_type, payload, redelivery_of) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (delivery_id) DO NOTHING")
+        .bind(id)
+        .bind(delivery_id)
+        .bind(event_type)
+        .bind(payload)
+        .bind(redelivery_of)
+        .execute(&mut sql_connection).await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// takes over a delivery whose last attempt failed, or was abandoned before stale_before.
+// received_at is reset so the new attempt is not taken for abandoned itself. returns false if another attempt claimed it first
+pub async fn claim_failed_delivery(
+    pool: PgPool,
+    id: Uuid,
+    stale_before: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    let claimed = sqlx::query("UPDATE constructum.webhook_deliveries SET error = NULL, completed_at = NULL, received_at = now() WHERE id = $1 AND (error IS NOT NULL OR (completed_at IS NULL AND received_at < $2)) RETURNING id")
+        .bind(id)
+        .bind(stale_before)
+        .fetch_optional(&mut sql_connection).await?;
+    Ok(claimed.is_some())
+}
+
+pub async fn complete_delivery(
+    pool: PgPool,
+    id: Uuid,
+    repo_id: Option<Uuid>,
+    job_id: Option<Uuid>,
+    error: Option<String>,
+) -> Result<(), sqlx::Error> {
+    let mut sql_connection = pool.acquire().await?;
+    sqlx::query("UPDATE constructum.webhook_deliveries SET repo_id = COALESCE($2, repo_id), job_id = $3, error = $4, completed_at = now() WHERE id = $1")
+        .bind(id)
+        .bind(repo_id)
+        .bind(job_id)
+        .bind(error)
+        .execute(&mut sql_connection).await?;
+    Ok(())
+}
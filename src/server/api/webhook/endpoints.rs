@@ -0,0 +1,44 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{server::error::ConstructumServerError, ConstructumServerState};
+
+use super::{error::ConstructumWebhookError, DeliveryInfo, WebhookResult};
+
+pub async fn list_deliveries(
+    Path(repo_id): Path<Uuid>,
+    State(state): State<ConstructumServerState>,
+) -> Result<Json<Vec<DeliveryInfo>>, ConstructumServerError> {
+    // checking for existence
+    let _repo_ref = crate::server::api::repo::db::get_repo_optional(repo_id, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoRepoFound)?;
+
+    let deliveries = super::db::list_deliveries_for_repo(repo_id, state.postgres()).await?;
+
+    Ok(Json(deliveries))
+}
+
+#[tracing::instrument(skip(state))]
+pub async fn redeliver(
+    Path((repo_id, delivery_id)): Path<(Uuid, Uuid)>,
+    State(state): State<ConstructumServerState>,
+) -> Result<Json<WebhookResult>, ConstructumWebhookError> {
+    let original = super::db::get_delivery(repo_id, delivery_id, state.postgres())
+        .await
+        .map_err(ConstructumServerError::from)?
+        .ok_or(ConstructumServerError::NoDeliveryFound)?;
+
+    // a redelivery is its own delivery so the original attempt stays in the log untouched
+    let id = Uuid::new_v4();
+    super::db::record_delivery(state.postgres(), id, &id.to_string(), &original.event_type, &original.payload, Some(original.id))
+        .await
+        .map_err(ConstructumServerError::from)?;
+
+    let job_uuid = super::process_delivery(id, &original.event_type, &original.payload, state).await?;
+
+    Ok(Json(WebhookResult { job_uuid }))
+}
@@ -8,12 +8,23 @@ use crate::server::error::ConstructumServerError;
 #[derive(Debug)]
 pub enum ConstructumWebhookError {
     ServerError(ConstructumServerError),
+    MalformedPayload(serde_json::Error),
+    InvalidToken,
+    NoWebhookSecret,
+    UnsupportedEvent(String),
+    // another attempt at the delivery with this ID did not finish in time
+    DeliveryInProgress(String),
 }
 
 impl Display for ConstructumWebhookError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ConstructumWebhookError::ServerError(server_e) => write!(f, "Webhook: {server_e}"),
+            ConstructumWebhookError::MalformedPayload(json) => write!(f, "Webhook: Malformed Payload: {json}"),
+            ConstructumWebhookError::InvalidToken => write!(f, "Webhook: Missing or invalid webhook token"),
+            ConstructumWebhookError::NoWebhookSecret => write!(f, "Webhook: No webhook secret is configured to verify the token against"),
+            ConstructumWebhookError::UnsupportedEvent(event) => write!(f, "Webhook: Unsupported event: {event}"),
+            ConstructumWebhookError::DeliveryInProgress(delivery_id) => write!(f, "Webhook: Delivery {delivery_id} is still being processed"),
         }
     }
 }
@@ -22,9 +33,17 @@ impl Error for ConstructumWebhookError {}
 
 impl IntoResponse for ConstructumWebhookError {
     fn into_response(self) -> Response {
+        let status = match self {
+            ConstructumWebhookError::ServerError(server_e) => return server_e.into_response(),
+            ConstructumWebhookError::MalformedPayload(_) => StatusCode::BAD_REQUEST,
+            ConstructumWebhookError::InvalidToken => StatusCode::UNAUTHORIZED,
+            ConstructumWebhookError::NoWebhookSecret => StatusCode::SERVICE_UNAVAILABLE,
+            ConstructumWebhookError::UnsupportedEvent(_) => StatusCode::BAD_REQUEST,
+            ConstructumWebhookError::DeliveryInProgress(_) => StatusCode::SERVICE_UNAVAILABLE,
+        };
         let resp_body = format!("{self}");
 
-        (StatusCode::INTERNAL_SERVER_ERROR, resp_body).into_response()
+        (status, resp_body).into_response()
     }
 }
 
@@ -32,4 +51,10 @@ impl From<ConstructumServerError> for ConstructumWebhookError {
     fn from(value: ConstructumServerError) -> Self {
         ConstructumWebhookError::ServerError(value)
     }
-}
\ No newline at end of file
+}
+
+impl From<serde_json::Error> for ConstructumWebhookError {
+    fn from(value: serde_json::Error) -> Self {
+        ConstructumWebhookError::MalformedPayload(value)
+    }
+}
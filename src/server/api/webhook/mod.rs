@@ -1,34 +1,234 @@
-use axum::{Json, extract::State, routing::post};
+use std::time::Duration;
+
+use axum::{Json, extract::State, http::HeaderMap, routing::{get, post}};
 use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use subtle::ConstantTimeEq;
+use tokio::time::Instant;
+use tracing::error;
 
 use uuid::Uuid;
 
-use crate::{ConstructumServerState, server::{self, CreateJobPayload}};
+use crate::{ConstructumServerState, config::GitForge, pipeline::has_skip_directive, server::{self, api::{job::JobTrigger, repo}, error::ConstructumServerError, CreateJobPayload}};
 
-use self::{error::ConstructumWebhookError, payload::GitWebhookPayload};
+use self::{error::ConstructumWebhookError, payload::{GitWebhookPayload, GitlabPushPayload, GitlabMergeRequestPayload}};
 
+pub mod db;
+pub mod endpoints;
 pub mod error;
+mod model;
 pub mod payload;
 
+#[cfg(test)]
+mod tests;
+
+pub use self::model::*;
+
+const NULL_SHA: &str = "0000000000000000000000000000000000000000";
+
+// how long a retried delivery waits for an earlier attempt that is still creating its job
+const DELIVERY_WAIT_SECS: u64 = 20;
+const DELIVERY_POLL_MILLIS: u64 = 250;
+// how long before an attempt that never finished is presumed dead and may be taken over
+const DELIVERY_STALE_SECS: i64 = 300;
+
 #[axum_macros::debug_handler]
 pub async fn webhook(
     State(state): State<ConstructumServerState>,
-    Json(payload): Json<GitWebhookPayload>,
+    headers: HeaderMap,
+    body: String,
 ) -> axum::response::Result<Json<WebhookResult>, ConstructumWebhookError> {
-    let create_job_payload = CreateJobPayload::new(payload.repository.id, payload.repository.html_url, payload.repository.name, payload.after);
-    let pipeline_uuid = server::create_job(create_job_payload, state).await?;
+    let forge = state.git_forge();
+    if forge == GitForge::Gitlab {
+        verify_gitlab_token(&headers, state.webhook_secret())?;
+    }
+
+    let event_type = event_type(forge, &headers);
+    let delivery_id = delivery_id(forge, &headers).unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    // forges retry deliveries they believe timed out, so hand back whatever the first attempt produced,
+    // waiting for it if it is still running
+    let deadline = Instant::now() + Duration::from_secs(DELIVERY_WAIT_SECS);
+    let id = loop {
+        let stale_before = Utc::now() - chrono::Duration::seconds(DELIVERY_STALE_SECS);
+        match db::get_delivery_by_delivery_id(&delivery_id, state.postgres()).await.map_err(ConstructumServerError::from)? {
+            None => {
+                let id = Uuid::new_v4();
+                // loses to a concurrent attempt at the same delivery, which is then waited on
+                if db::record_delivery(state.postgres(), id, &delivery_id, &event_type, &body, None).await.map_err(ConstructumServerError::from)? {
+                    break id;
+                }
+            },
+            // the previous attempt failed or was abandoned, so whichever retry claims it first gets to try again
+            Some(existing) if existing.error.is_some() || existing.is_abandoned(stale_before) => {
+                if db::claim_failed_delivery(state.postgres(), existing.id, stale_before).await.map_err(ConstructumServerError::from)? {
+                    break existing.id;
+                }
+            },
+            Some(existing) if existing.completed_at.is_some() => return Ok(Json(WebhookResult { job_uuid: existing.job_id })),
+            Some(_) => {},
+        }
+
+        if Instant::now() >= deadline {
+            return Err(ConstructumWebhookError::DeliveryInProgress(delivery_id));
+        }
+        tokio::time::sleep(Duration::from_millis(DELIVERY_POLL_MILLIS)).await;
+    };
+
+    let job_uuid = process_delivery(id, &event_type, &body, state).await?;
 
     Ok(Json(WebhookResult {
-        job_uuid: pipeline_uuid
+        job_uuid
     }))
 }
 
+pub(crate) async fn process_delivery(id: Uuid, event_type: &str, body: &str, state: ConstructumServerState) -> Result<Option<Uuid>, ConstructumWebhookError> {
+    let event = match parse_event(state.git_forge(), event_type, body) {
+        Ok(event) => event,
+        Err(err) => return Err(fail_delivery(&state, id, None, err).await),
+    };
+
+    let repo_id = match repo::db::get_repo_by_git_id(event.git_repo_id, state.postgres()).await {
+        Ok(repo_info) => repo_info.map(|x| x.repo_uuid),
+        Err(err) => return Err(fail_delivery(&state, id, None, ConstructumServerError::from(err).into()).await),
+    };
+
+    let job_uuid = match event.create_job_payload {
+        Some(create_job_payload) => match server::create_job(create_job_payload, state.clone()).await {
+            Ok(job_uuid) => job_uuid,
+            Err(err) => return Err(fail_delivery(&state, id, repo_id, err.into()).await),
+        },
+        None => None,
+    };
+
+    db::complete_delivery(state.postgres(), id, repo_id, job_uuid, None).await.map_err(ConstructumServerError::from)?;
+
+    Ok(job_uuid)
+}
+
+async fn fail_delivery(state: &ConstructumServerState, id: Uuid, repo_id: Option<Uuid>, err: ConstructumWebhookError) -> ConstructumWebhookError {
+    if let Err(db_err) = db::complete_delivery(state.postgres(), id, repo_id, None, Some(format!("{err}"))).await {
+        error!("Failed to record webhook delivery {id}: {db_err}");
+    }
+    err
+}
+
+fn event_type(forge: GitForge, headers: &HeaderMap) -> String {
+    let header_name = match forge {
+        GitForge::Gitea => "X-Gitea-Event",
+        GitForge::Gitlab => "X-Gitlab-Event",
+    };
+    headers.get(header_name).and_then(|x| x.to_str().ok()).unwrap_or_default().to_owned()
+}
+
+fn delivery_id(forge: GitForge, headers: &HeaderMap) -> Option<String> {
+    let header_names: &[&str] = match forge {
+        GitForge::Gitea => &["X-Gitea-Delivery"],
+        // Idempotency-Key is stable across GitLab's retries, the event UUID is the fallback for older instances
+        GitForge::Gitlab => &["Idempotency-Key", "X-Gitlab-Event-UUID"],
+    };
+    header_names.iter().find_map(|x| headers.get(*x).and_then(|y| y.to_str().ok()).map(String::from))
+}
+
+// without a secret anyone could start builds, so GitLab hooks are refused until one is configured
+fn verify_gitlab_token(headers: &HeaderMap, secret: Option<String>) -> Result<(), ConstructumWebhookError> {
+    let secret = secret.ok_or(ConstructumWebhookError::NoWebhookSecret)?;
+    let token = headers.get("X-Gitlab-Token").map(|x| x.as_bytes()).unwrap_or_default();
+    if bool::from(token.ct_eq(secret.as_bytes())) {
+        Ok(())
+    } else {
+        Err(ConstructumWebhookError::InvalidToken)
+    }
+}
+
+struct WebhookEvent {
+    git_repo_id: i32,
+    // None for events that are valid but should not start a build, e.g. branch deletions, closed merge requests or skipped commits
+    create_job_payload: Option<CreateJobPayload>,
+}
+
+fn parse_event(forge: GitForge, event_type: &str, body: &str) -> Result<WebhookEvent, ConstructumWebhookError> {
+    match forge {
+        GitForge::Gitea => parse_gitea_event(event_type, body),
+        GitForge::Gitlab => parse_gitlab_event(event_type, body),
+    }
+}
+
+fn parse_gitea_event(event_type: &str, body: &str) -> Result<WebhookEvent, ConstructumWebhookError> {
+    // push is the only event we subscribe to; be lenient if the header is missing
+    if !event_type.is_empty() && event_type != "push" {
+        return Err(ConstructumWebhookError::UnsupportedEvent(event_type.to_owned()));
+    }
+
+    let payload: GitWebhookPayload = serde_json::from_str(body)?;
+    let git_repo_id = payload.repository.id;
+    if payload.head_commit_message().map(has_skip_directive).unwrap_or(false) || payload.after == NULL_SHA {
+        return Ok(WebhookEvent { git_repo_id, create_job_payload: None });
+    }
+
+    let commit_files = payload.commit_files();
+    let mut create_job_payload = CreateJobPayload::new(payload.repository.id, payload.repository.html_url, payload.repository.name, payload.after, Some(payload.git_reference), JobTrigger::Push);
+    create_job_payload.before = Some(payload.before).filter(|x| x != NULL_SHA);
+    create_job_payload.commit_files = commit_files;
+
+    Ok(WebhookEvent { git_repo_id, create_job_payload: Some(create_job_payload) })
+}
+
+fn parse_gitlab_event(event_type: &str, body: &str) -> Result<WebhookEvent, ConstructumWebhookError> {
+    match event_type {
+        "Push Hook" | "Tag Push Hook" => {
+            let payload: GitlabPushPayload = serde_json::from_str(body)?;
+            let git_repo_id = payload.project.id;
+            if payload.head_commit_message().map(has_skip_directive).unwrap_or(false) {
+                return Ok(WebhookEvent { git_repo_id, create_job_payload: None });
+            }
+
+            let commit_files = payload.commit_files();
+            let commit_hash = payload.checkout_sha.unwrap_or(payload.after);
+            if commit_hash == NULL_SHA {
+                return Ok(WebhookEvent { git_repo_id, create_job_payload: None });
+            }
+
+            let name = payload.project.path();
+            let mut create_job_payload = CreateJobPayload::new(payload.project.id, payload.project.git_http_url, name, commit_hash, Some(payload.git_reference), JobTrigger::Push);
+            create_job_payload.before = Some(payload.before).filter(|x| x != NULL_SHA);
+            create_job_payload.commit_files = commit_files;
+
+            Ok(WebhookEvent { git_repo_id, create_job_payload: Some(create_job_payload) })
+        },
+        "Merge Request Hook" => {
+            let payload: GitlabMergeRequestPayload = serde_json::from_str(body)?;
+            let git_repo_id = payload.project.id;
+            let attrs = payload.object_attributes;
+            // updates without oldrev only touched the title, labels or description
+            let has_new_commits = match attrs.action.as_deref() {
+                Some("open") | Some("reopen") => true,
+                Some("update") => attrs.oldrev.is_some(),
+                _ => false,
+            };
+            // commits of merge requests from forks are not in the project the job clones
+            let is_fork = attrs.source_project_id != attrs.target_project_id;
+            if attrs.state != "opened" || !has_new_commits || is_fork || has_skip_directive(&attrs.last_commit.message) {
+                return Ok(WebhookEvent { git_repo_id, create_job_payload: None });
+            }
+
+            let name = payload.project.path();
+            let create_job_payload = CreateJobPayload::new(payload.project.id, payload.project.git_http_url, name, attrs.last_commit.id, Some(format!("refs/heads/{}", attrs.source_branch)), JobTrigger::Push);
+
+            Ok(WebhookEvent { git_repo_id, create_job_payload: Some(create_job_payload) })
+        },
+        other => Err(ConstructumWebhookError::UnsupportedEvent(other.to_owned())),
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WebhookResult {
-    job_uuid: Uuid,
+    job_uuid: Option<Uuid>,
 }
 
 pub fn register_module(router: axum::Router<ConstructumServerState, axum::body::Body>) -> axum::Router<ConstructumServerState, axum::body::Body> {
     router
         .route("/webhook", post(webhook))
-}
\ No newline at end of file
+        .route("/repos/:repo_id/deliveries", get(self::endpoints::list_deliveries))
+        .route("/repos/:repo_id/deliveries/:delivery_id/redeliver", post(self::endpoints::redeliver))
+}
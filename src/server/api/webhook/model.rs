@@ -0,0 +1,44 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{postgres::PgRow, Row};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct DeliveryInfo {
+    pub id: Uuid,
+    pub delivery_id: String,
+    pub repo_id: Option<Uuid>,
+    pub event_type: String,
+    pub payload: String,
+    pub job_id: Option<Uuid>,
+    pub error: Option<String>,
+    pub redelivery_of: Option<Uuid>,
+    pub received_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl<'r> sqlx::FromRow<'r, PgRow> for DeliveryInfo {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let id: Uuid = row.try_get("id")?;
+        let delivery_id: String = row.try_get("delivery_id")?;
+        let repo_id: Option<Uuid> = row.try_get("repo_id")?;
+        let event_type: String = row.try_get("event_type")?;
+        let payload: String = row.try_get("payload")?;
+        let job_id: Option<Uuid> = row.try_get("job_id")?;
+        let error: Option<String> = row.try_get("error")?;
+        let redelivery_of: Option<Uuid> = row.try_get("redelivery_of")?;
+        let received_at: DateTime<Utc> = row.try_get("received_at")?;
+        let completed_at: Option<DateTime<Utc>> = row.try_get("completed_at")?;
+
+        Ok(
+            DeliveryInfo { id, delivery_id, repo_id, event_type, payload, job_id, error, redelivery_of, received_at, completed_at }
+        )
+    }
+}
+
+impl DeliveryInfo {
+    // an attempt that neither completed nor failed by the cutoff died along with its server, and nothing will ever finish it
+    pub fn is_abandoned(&self, stale_before: DateTime<Utc>) -> bool {
+        self.completed_at.is_none() && self.error.is_none() && self.received_at < stale_before
+    }
+}
@@ -7,24 +7,40 @@ pub struct GitWebhookPayload {
     secret: Option<String>,
     #[serde(rename(deserialize = "ref"))]
     pub git_reference: String,
-    before: String,
+    pub before: String,
     pub after: String,
     compare_url: String,
-    commits: Vec<CommitWebhookPayload>,
+    pub commits: Vec<CommitWebhookPayload>,
     pub repository: RepositoryWebhookPayload,
     pusher: UserWebhookPayload,
     sender: UserWebhookPayload,
 
 }
 
+impl GitWebhookPayload {
+    pub fn head_commit_message(&self) -> Option<&str> {
+        self.commits.iter().find(|x| x.id == self.after).or(self.commits.last()).map(|x| x.message.as_str())
+    }
+
+    pub fn commit_files(&self) -> Vec<String> {
+        self.commits.iter().flat_map(|x| x.added.iter().chain(x.removed.iter()).chain(x.modified.iter()).cloned()).collect()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CommitWebhookPayload {
-    id: String,
-    message: String,
+    pub id: String,
+    pub message: String,
     url: String,
     author: CommitUserWebhookPayload,
     committer: CommitUserWebhookPayload,
     timestamp: String,
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    #[serde(default)]
+    pub modified: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -64,4 +80,81 @@ pub struct UserWebhookPayload {
     email: String,
     avatar_url: String,
     username: String,
-}
\ No newline at end of file
+}
+#[derive(Debug, Deserialize)]
+pub struct GitlabPushPayload {
+    pub object_kind: String,
+    #[serde(rename(deserialize = "ref"))]
+    pub git_reference: String,
+    pub before: String,
+    pub after: String,
+    pub checkout_sha: Option<String>,
+    pub project_id: i32,
+    pub project: GitlabProjectWebhookPayload,
+    pub commits: Vec<GitlabCommitWebhookPayload>,
+    total_commits_count: u64,
+}
+
+impl GitlabPushPayload {
+    pub fn head_commit_message(&self) -> Option<&str> {
+        let head = self.checkout_sha.as_ref().unwrap_or(&self.after);
+        self.commits.iter().find(|x| &x.id == head).or(self.commits.last()).map(|x| x.message.as_str())
+    }
+
+    pub fn commit_files(&self) -> Vec<String> {
+        self.commits.iter().flat_map(|x| x.added.iter().chain(x.removed.iter()).chain(x.modified.iter()).cloned()).collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitlabMergeRequestPayload {
+    pub object_kind: String,
+    pub project: GitlabProjectWebhookPayload,
+    pub object_attributes: GitlabMergeRequestAttributes,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitlabMergeRequestAttributes {
+    pub iid: u64,
+    pub source_branch: String,
+    pub target_branch: String,
+    pub state: String,
+    pub action: Option<String>,
+    // only set on updates that pushed new commits to the source branch
+    pub oldrev: Option<String>,
+    pub source_project_id: i32,
+    pub target_project_id: i32,
+    pub last_commit: GitlabCommitWebhookPayload,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitlabCommitWebhookPayload {
+    pub id: String,
+    pub message: String,
+    url: String,
+    timestamp: String,
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    #[serde(default)]
+    pub modified: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitlabProjectWebhookPayload {
+    pub id: i32,
+    pub name: String,
+    pub web_url: String,
+    pub git_http_url: String,
+    pub git_ssh_url: String,
+    pub path_with_namespace: String,
+    pub default_branch: String,
+}
+
+impl GitlabProjectWebhookPayload {
+    // the name the project is registered under, which unlike its display name is safe to use as a directory
+    pub fn path(&self) -> String {
+        self.path_with_namespace.rsplit('/').next().unwrap_or(&self.path_with_namespace).to_owned()
+    }
+}
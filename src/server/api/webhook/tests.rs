@@ -0,0 +1,36 @@
+use chrono::{Duration, TimeZone, Utc};
+use uuid::Uuid;
+
+use super::DeliveryInfo;
+
+fn delivery(received_minutes_ago: i64) -> DeliveryInfo {
+    let now = Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+    DeliveryInfo {
+        id: Uuid::new_v4(),
+        delivery_id: String::from("delivery"),
+        repo_id: None,
+        event_type: String::from("push"),
+        payload: String::from("{}"),
+        job_id: None,
+        error: None,
+        redelivery_of: None,
+        received_at: now - Duration::minutes(received_minutes_ago),
+        completed_at: None,
+    }
+}
+
+#[test]
+fn test_unfinished_delivery_is_abandoned_after_the_cutoff() {
+    let stale_before = Utc.with_ymd_and_hms(2023, 5, 1, 11, 55, 0).unwrap();
+
+    assert!(delivery(10).is_abandoned(stale_before));
+    assert!(!delivery(1).is_abandoned(stale_before));
+
+    let mut completed = delivery(10);
+    completed.completed_at = Some(stale_before);
+    assert!(!completed.is_abandoned(stale_before));
+
+    let mut failed = delivery(10);
+    failed.error = Some(String::from("failed"));
+    assert!(!failed.is_abandoned(stale_before));
+}
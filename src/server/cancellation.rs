@@ -0,0 +1,51 @@
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{kube::{delete_pipeline_jobs, delete_pvc}, ConstructumServerState};
+
+use super::{api::{job, step::{self, model::StepStatus}}, error::ConstructumServerError, job_spawning::report_job_status};
+
+// marks the job Cancelled and tears down whatever it has running in the cluster.
+// returns false if the job had already finished.
+pub async fn cancel_job(job_uuid: Uuid, state: &ConstructumServerState) -> Result<bool, ConstructumServerError> {
+    // flip the status first so the client and server_job see the cancellation rather than a failure
+    if !job::db::cancel_job(state.postgres(), job_uuid).await? {
+        return Ok(false);
+    }
+
+    tear_down_job(job_uuid, StepStatus::Cancelled, state).await?;
+
+    Ok(true)
+}
+
+// removes the Jobs and workspace of a job the server finished from outside, and stops watching it
+pub(super) async fn tear_down_job(job_uuid: Uuid, step_status: StepStatus, state: &ConstructumServerState) -> Result<(), ConstructumServerError> {
+    let workspace_uuid = job::db::get_job(job_uuid, state.postgres()).await?.workspace_uuid();
+    let namespace = &state.kube_settings().namespace;
+    delete_pipeline_jobs(&job_uuid.to_string(), namespace).await?;
+    delete_pvc(&workspace_uuid.to_string(), namespace).await?;
+    step::db::finish_unfinished_steps(state.postgres(), job_uuid, step_status).await?;
+
+    state.current_jobs().write().expect("lock poisoned").remove(&job_uuid);
+
+    if let Err(err) = report_job_status(job_uuid, state).await {
+        error!("Failed to report job status: {err}");
+    }
+
+    Ok(())
+}
+
+// cancels the unfinished push builds of a branch that the given job replaces
+pub async fn cancel_superseded_jobs(job_uuid: Uuid, repo_id: Uuid, git_ref: &str, state: &ConstructumServerState) -> Result<(), ConstructumServerError> {
+    let superseded = job::db::list_superseded_jobs(state.postgres(), repo_id, git_ref, job_uuid).await?;
+
+    for old_job in superseded {
+        match cancel_job(old_job.job_uuid, state).await {
+            Ok(true) => info!("job {} was superseded by {job_uuid}", old_job.job_uuid),
+            Ok(false) => {},
+            Err(err) => error!("Failed to cancel superseded job {}: {err}", old_job.job_uuid),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use futures::future::BoxFuture;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{git, pipeline::{PipelineStatus, PipelineTrigger}, ConstructumServerState};
+
+use super::{api::{job::{self, JobTrigger}, repo}, error::ConstructumServerError, job_spawning::{payload_for_revision, read_pipeline}, create_job, JobRevision};
+
+// starts the `triggers:` of a successfully completed job.
+// a failing trigger is logged and does not stop the others from starting.
+// boxed because the jobs this starts spawn their own server_job, which calls back into here.
+pub fn trigger_downstream_jobs(job_uuid: Uuid, state: &ConstructumServerState) -> BoxFuture<'_, Result<(), ConstructumServerError>> {
+    Box::pin(async move {
+        let job_info = job::db::get_job(job_uuid, state.postgres()).await?;
+        if job_info.status != PipelineStatus::Complete {
+            return Ok(());
+        }
+
+        let repo_info = repo::db::get_repo(job_info.repo_id, state.postgres()).await?;
+        let pipeline = read_pipeline(repo_info.repo_url, repo_info.repo_name, job_info.commit_id, state).await?;
+        let triggers = match pipeline.triggers {
+            Some(triggers) if !triggers.is_empty() => triggers,
+            _ => return Ok(()),
+        };
+
+        let ancestor_repos = job::db::list_ancestor_repos(job_uuid, state.postgres()).await?;
+
+        for trigger in triggers {
+            match trigger_downstream_job(job_uuid, &ancestor_repos, &trigger, state).await {
+                Ok(Some(child_uuid)) => info!("job {job_uuid} triggered job {child_uuid} in {}", trigger.repo),
+                Ok(None) => info!("job {job_uuid} did not start a job in {}", trigger.repo),
+                Err(err) => error!("job {job_uuid} failed to trigger {}: {err}", trigger.repo),
+            }
+        }
+
+        Ok(())
+    })
+}
+
+async fn trigger_downstream_job(parent_uuid: Uuid, ancestor_repos: &[Uuid], trigger: &PipelineTrigger, state: &ConstructumServerState) -> Result<Option<Uuid>, ConstructumServerError> {
+    let (repo_owner, repo_name) = repo::split_repo_path(&trigger.repo)
+        .ok_or_else(|| ConstructumServerError::InvalidJobRequest(format!("trigger repo {} is not of the form owner/name", trigger.repo)))?;
+
+    let repo_info = repo::db::get_repo_by_name(repo_owner, repo_name, state.postgres())
+        .await?
+        .ok_or(ConstructumServerError::NoRepoFound)?;
+
+    // a chain that leads back into a repository it already built would never stop
+    if ancestor_repos.contains(&repo_info.repo_uuid) {
+        return Err(ConstructumServerError::InvalidJobRequest(format!("trigger of {} would form a cycle", trigger.repo)));
+    }
+
+    let revision = match (&trigger.branch, &trigger.tag) {
+        (Some(_), Some(_)) => return Err(ConstructumServerError::InvalidJobRequest(String::from("a trigger takes at most one of branch or tag"))),
+        (Some(branch), None) => JobRevision::Branch(branch.clone()),
+        (None, Some(tag)) => JobRevision::Tag(tag.clone()),
+        (None, None) => JobRevision::Branch(
+            git::default_branch(Path::new(&state.build_cache_location()), repo_info.repo_url.clone(), repo_info.repo_name.clone()).await?
+        ),
+    };
+
+    let mut payload = payload_for_revision(repo_info, revision, JobTrigger::Upstream, trigger.parameters.clone(), state).await?;
+    payload.parent_job = Some(parent_uuid);
+
+    create_job(payload, state.clone()).await
+}
@@ -6,7 +6,7 @@ use axum::{
 };
 use serde::Serialize;
 
-use crate::{git, redis::error::ConstructumRedisError};
+use crate::{git, kube::error::ConstructumKubeError, pipeline::PipelineParameterError, redis::error::ConstructumRedisError};
 
 #[derive(Debug)]
 pub enum ConstructumServerError {
@@ -15,6 +15,7 @@ pub enum ConstructumServerError {
     JSONSerialize(serde_json::Error),
     Git(git::GitError),
     Kubernetes(kube::Error),
+    KubeResource(ConstructumKubeError),
     Sql(sqlx::Error),
     HeaderToStrError(reqwest::header::ToStrError),
     BadAuthorization,
@@ -22,6 +23,19 @@ pub enum ConstructumServerError {
     NoRepoFound,
     RepoAlreadyRegistered,
     Redis(ConstructumRedisError),
+    InvalidParameters(PipelineParameterError),
+    InvalidJobRequest(String),
+    InvalidSchedule(String),
+    InvalidRepoSettings(String),
+    NoScheduleFound,
+    NoJobFound,
+    JobAlreadyFinished,
+    JobNotFinished,
+    JobNotQueued,
+    NotResumable(String),
+    NoDeliveryFound,
+    InvalidPathFilter(globset::Error),
+    ForgeRequestFailed(String),
 }
 
 impl Display for ConstructumServerError {
@@ -33,6 +47,7 @@ impl Display for ConstructumServerError {
             }
             ConstructumServerError::Git(git) => write!(f, "Server: Git Error: {git}"),
             ConstructumServerError::Kubernetes(kube) => write!(f, "Server: Kube Error: {kube}"),
+            ConstructumServerError::KubeResource(kube) => write!(f, "Server: {kube}"),
             ConstructumServerError::JSONSerialize(json) => {
                 write!(f, "Server: JSON Serialize Error: {json}")
             }
@@ -50,6 +65,31 @@ impl Display for ConstructumServerError {
                 write!(f, "Server: Repo Already Registered")
             }
             ConstructumServerError::Redis(red) => write!(f, "Server: Redis Error: {red}"),
+            ConstructumServerError::InvalidParameters(param) => write!(f, "Server: {param}"),
+            ConstructumServerError::InvalidJobRequest(reason) => {
+                write!(f, "Server: Invalid Job Request: {reason}")
+            }
+            ConstructumServerError::InvalidSchedule(reason) => {
+                write!(f, "Server: Invalid Schedule: {reason}")
+            }
+            ConstructumServerError::InvalidRepoSettings(reason) => {
+                write!(f, "Server: Invalid Repo Settings: {reason}")
+            }
+            ConstructumServerError::NoScheduleFound => write!(f, "Server: Schedule Not Found"),
+            ConstructumServerError::NoJobFound => write!(f, "Server: Job Not Found"),
+            ConstructumServerError::JobAlreadyFinished => write!(f, "Server: Job Already Finished"),
+            ConstructumServerError::JobNotFinished => write!(f, "Server: Job Not Finished"),
+            ConstructumServerError::JobNotQueued => write!(f, "Server: Job Not Queued"),
+            ConstructumServerError::NotResumable(reason) => {
+                write!(f, "Server: Job Cannot Be Resumed: {reason}")
+            }
+            ConstructumServerError::NoDeliveryFound => write!(f, "Server: Webhook Delivery Not Found"),
+            ConstructumServerError::InvalidPathFilter(glob) => {
+                write!(f, "Server: Invalid Path Filter: {glob}")
+            }
+            ConstructumServerError::ForgeRequestFailed(reason) => {
+                write!(f, "Server: Git Forge Request Failed: {reason}")
+            }
         }
     }
 }
@@ -63,10 +103,28 @@ impl IntoResponse for ConstructumServerError {
             error: String,
         }
 
+        let status = match self {
+            ConstructumServerError::InvalidParameters(_)
+            | ConstructumServerError::InvalidJobRequest(_)
+            | ConstructumServerError::InvalidSchedule(_)
+            | ConstructumServerError::InvalidRepoSettings(_)
+            | ConstructumServerError::InvalidPathFilter(_)
+            | ConstructumServerError::Git(git::GitError::UnknownRevision(_)) => StatusCode::BAD_REQUEST,
+            ConstructumServerError::NoScheduleFound
+            | ConstructumServerError::NoDeliveryFound
+            | ConstructumServerError::NoJobFound => StatusCode::NOT_FOUND,
+            ConstructumServerError::JobAlreadyFinished
+            | ConstructumServerError::JobNotFinished
+            | ConstructumServerError::JobNotQueued
+            | ConstructumServerError::NotResumable(_) => StatusCode::CONFLICT,
+            ConstructumServerError::ForgeRequestFailed(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
         let resp_body = format!("{self}");
 
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            status,
             serde_json::to_string(&Error { error: resp_body })
                 .expect("failed to construct serde response"),
         )
@@ -98,6 +156,12 @@ impl From<kube::Error> for ConstructumServerError {
     }
 }
 
+impl From<ConstructumKubeError> for ConstructumServerError {
+    fn from(value: ConstructumKubeError) -> Self {
+        ConstructumServerError::KubeResource(value)
+    }
+}
+
 impl From<serde_json::Error> for ConstructumServerError {
     fn from(value: serde_json::Error) -> Self {
         ConstructumServerError::JSONSerialize(value)
@@ -126,4 +190,16 @@ impl From<ConstructumRedisError> for ConstructumServerError {
     fn from(value: ConstructumRedisError) -> Self {
         Self::Redis(value)
     }
-}
\ No newline at end of file
+}
+
+impl From<globset::Error> for ConstructumServerError {
+    fn from(value: globset::Error) -> Self {
+        Self::InvalidPathFilter(value)
+    }
+}
+
+impl From<PipelineParameterError> for ConstructumServerError {
+    fn from(value: PipelineParameterError) -> Self {
+        Self::InvalidParameters(value)
+    }
+}
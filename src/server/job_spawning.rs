@@ -1,47 +1,198 @@
-use std::path::Path;
+use std::{path::Path, collections::HashMap};
 
 use k8s_openapi::api::{core::v1::PersistentVolumeClaim, batch::v1::Job};
-use kube::{Api, api::PostParams, runtime::wait::{await_condition, conditions}};
+use kube::{Api, api::PostParams};
+use chrono::{DateTime, Utc};
 use tokio::{task, io::AsyncReadExt};
-use tracing::error;
+use tracing::{error, info};
 use uuid::Uuid;
 
-use crate::{ConstructumServerState, pipeline::Pipeline, server::error::ConstructumServerError, git, kube::{build_client_pvc, put_pod_logs_to_s3, delete_job, delete_pvc}, redis::logs_to_redis};
+use crate::{ConstructumServerState, pipeline::{Pipeline, PipelineStatus}, server::error::ConstructumServerError, git, kube::{await_job, build_client_pvc, client_job_name, put_pod_logs_to_s3, delete_job, delete_pvc, JobWaitOutcome, ResourceIdentity, CLIENT_CONTAINER_NAME}, redis::logs_to_redis};
 
-use super::api::job::db::list_unfinished_jobs;
+use super::api::{job::JobTrigger, repo::{RepoInfo, WorkspaceMode}, step::model::StepStatus};
+
+// the vault injector only renders secrets into pods as they are created, which the steps of a pod workspace are not
+pub const POD_WORKSPACE_SECRETS: &str = "pipelines that use secrets cannot run in a Pod workspace, use the Volume workspace mode";
 
 pub struct CreateJobPayload {
     pub repo_id: i32,
     pub html_url: String,
     pub name: String,
-    pub commit_hash: String
+    pub commit_hash: String,
+    pub git_ref: Option<String>,
+    pub trigger: JobTrigger,
+    pub parameters: HashMap<String, serde_json::Value>,
+    // previous head of the ref, used to work out which files a push changed
+    pub before: Option<String>,
+    // files the forge reported as touched by the pushed commits, used if `before` cannot be diffed against
+    pub commit_files: Vec<String>,
+    // the job whose pipeline triggered this one, if any
+    pub parent_job: Option<Uuid>,
+    // set for re-runs, which reuse the original job's parameters and changed files
+    pub rerun_of: Option<Uuid>,
+    pub resume_from_step: Option<i32>,
+    pub workspace: Option<Uuid>,
+    // taken from the pipeline when the job is recorded
+    pub timeout_minutes: Option<i32>,
+    // set when the job is recorded if the repo debounces pushes
+    pub not_before: Option<DateTime<Utc>>,
+    // uid of the PipelineRun that requested the job
+    pub pipeline_run: Option<Uuid>,
 }
 
 impl CreateJobPayload {
-    pub fn new(repo_id: i32, html_url: String, name: String, commit_hash: String) -> CreateJobPayload {
-        CreateJobPayload { repo_id, html_url, name, commit_hash }
+    pub fn new(repo_id: i32, html_url: String, name: String, commit_hash: String, git_ref: Option<String>, trigger: JobTrigger) -> CreateJobPayload {
+        CreateJobPayload { repo_id, html_url, name, commit_hash, git_ref, trigger, parameters: HashMap::new(), before: None, commit_files: Vec::new(), parent_job: None, rerun_of: None, resume_from_step: None, workspace: None, timeout_minutes: None, not_before: None, pipeline_run: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum JobRevision {
+    Branch(String),
+    Tag(String),
+    Commit(String),
+}
+
+impl JobRevision {
+    // the build cache only has remote-tracking branches, so branches are resolved through origin
+    fn to_revision_and_ref(&self) -> (String, Option<String>) {
+        match self {
+            JobRevision::Branch(branch) => (format!("origin/{branch}"), Some(format!("refs/heads/{branch}"))),
+            JobRevision::Tag(tag) => (format!("refs/tags/{tag}"), Some(format!("refs/tags/{tag}"))),
+            JobRevision::Commit(commit) => (commit.clone(), None),
+        }
     }
 }
 
-pub async fn create_job(payload: CreateJobPayload, state: ConstructumServerState) -> Result<Uuid, ConstructumServerError> {
-    let pipeline_uuid = record_new_job_to_sql(payload, state.clone()).await?;
-    assign_job_to_k8s(pipeline_uuid, state).await?;
+// starts a job without a webhook by resolving the revision against the build cache first
+pub async fn create_job_for_revision(repo: RepoInfo, revision: JobRevision, trigger: JobTrigger, parameters: HashMap<String, serde_json::Value>, state: ConstructumServerState) -> Result<Option<Uuid>, ConstructumServerError> {
+    let payload = payload_for_revision(repo, revision, trigger, parameters, &state).await?;
+
+    create_job(payload, state).await
+}
+
+pub(super) async fn payload_for_revision(repo: RepoInfo, revision: JobRevision, trigger: JobTrigger, parameters: HashMap<String, serde_json::Value>, state: &ConstructumServerState) -> Result<CreateJobPayload, ConstructumServerError> {
+    if !repo.enabled {
+        return Err(ConstructumServerError::InvalidJobRequest(String::from("repository is not enabled")));
+    }
+
+    let (revision, git_ref) = revision.to_revision_and_ref();
+    let commit_hash = git::resolve_revision(
+        Path::new(&state.build_cache_location()),
+        repo.repo_url.clone(),
+        repo.repo_name.clone(),
+        revision,
+    )
+    .await?;
 
-    Ok(pipeline_uuid)
+    let mut payload = CreateJobPayload::new(repo.git_id, repo.repo_url, repo.repo_name, commit_hash, git_ref, trigger);
+    payload.parameters = parameters;
+
+    Ok(payload)
 }
 
-async fn record_new_job_to_sql(payload: CreateJobPayload, state: ConstructumServerState) -> Result<Uuid, ConstructumServerError> {
+// queues a job and hands it to the dispatcher.
+// returns None if the pipeline's path filters exclude every changed file
+pub async fn create_job(payload: CreateJobPayload, state: ConstructumServerState) -> Result<Option<Uuid>, ConstructumServerError> {
+    let branch_ref = supersedable_ref(&payload);
+
+    let (pipeline_uuid, repo_ref) = match record_new_job_to_sql(payload, state.clone()).await? {
+        Some(recorded) => recorded,
+        None => return Ok(None),
+    };
+
+    if let Some(branch_ref) = branch_ref.filter(|_| repo_ref.auto_cancel) {
+        super::cancel_superseded_jobs(pipeline_uuid, repo_ref.repo_uuid, &branch_ref, &state).await?;
+    }
+
+    if let Err(err) = report_job_status(pipeline_uuid, &state).await {
+        error!("Failed to report job status: {err}");
+    }
+
+    // the periodic dispatch picks the job up if this attempt fails
+    if let Err(err) = super::dispatch_queued_jobs(state).await {
+        error!("Failed to dispatch queued jobs: {err}");
+    }
+
+    Ok(Some(pipeline_uuid))
+}
+
+// only pushes to a branch can be superseded; tags and manual or scheduled runs always build
+fn supersedable_ref(payload: &CreateJobPayload) -> Option<String> {
+    payload.git_ref.clone()
+        .filter(|x| payload.trigger == JobTrigger::Push && x.starts_with("refs/heads/"))
+}
+
+async fn record_new_job_to_sql(mut payload: CreateJobPayload, state: ConstructumServerState) -> Result<Option<(Uuid, RepoInfo)>, ConstructumServerError> {
     // checking for existence
     let repo_ref = 
         super::api::repo::db::get_repo_by_git_id(payload.repo_id, state.postgres())
             .await?
             .ok_or(ConstructumServerError::NoRepoFound)?;
 
+    let pipeline = read_pipeline(payload.html_url.clone(), payload.name.clone(), payload.commit_hash.clone(), &state).await?;
+
+    // resumed jobs run in their old volume, whatever the repo uses now
+    if payload.workspace.is_none() && repo_ref.workspace_mode == WorkspaceMode::Pod && pipeline.uses_secrets() {
+        return Err(ConstructumServerError::InvalidJobRequest(String::from(POD_WORKSPACE_SECRETS)));
+    }
+
+    let (parameters, changed_files) = match payload.rerun_of {
+        // a re-run repeats the original as closely as possible, so nothing is resolved or filtered again
+        Some(original) => {
+            let original = super::api::job::db::get_job(original, state.postgres()).await?;
+            (original.parameters, original.changed_files)
+        },
+        None => {
+            let parameters = pipeline.resolve_parameters(&payload.parameters, payload.trigger.supplies_parameters())?;
+
+            let changed_files = resolve_changed_files(&payload, &state).await?;
+            if !pipeline.should_run_for(changed_files.as_deref())? {
+                info!("skipping {} at {}: no changed files match the pipeline's path filters", payload.name, payload.commit_hash);
+                return Ok(None);
+            }
+
+            (parameters, changed_files)
+        },
+    };
+
+    // with debouncing, a follow-up push gets the chance to cancel this job before it takes up any cluster resources
+    payload.not_before = Some(repo_ref.debounce_seconds)
+        .filter(|x| *x > 0 && repo_ref.auto_cancel && supersedable_ref(&payload).is_some())
+        .map(|x| Utc::now() + chrono::Duration::seconds(i64::from(x)));
+
+    payload.timeout_minutes = pipeline.timeout.map(|x| i32::try_from(x).unwrap_or(i32::MAX));
+
+    let pipeline_uuid = Uuid::new_v4();
+    
+    super::api::job::db::create_job(state.postgres(), pipeline_uuid, repo_ref.builds_executed+1, repo_ref.repo_uuid, payload, parameters, changed_files).await?;
+    super::api::repo::db::update_repo_seq(state.postgres(), repo_ref.repo_uuid, repo_ref.builds_executed+1).await?;
+
+    Ok(Some((pipeline_uuid, repo_ref)))
+}
+
+// the pipeline on the default branch of the repo, for vetting repo settings against
+pub(super) async fn read_default_pipeline(repo: &RepoInfo, state: &ConstructumServerState) -> Result<Pipeline, ConstructumServerError> {
+    let root = state.build_cache_location();
+    let branch = git::default_branch(Path::new(&root), repo.repo_url.clone(), repo.repo_name.clone()).await?;
+
+    read_branch_pipeline(repo, branch, state).await
+}
+
+// the pipeline at the tip of a branch, for vetting schedules against
+pub(super) async fn read_branch_pipeline(repo: &RepoInfo, branch: String, state: &ConstructumServerState) -> Result<Pipeline, ConstructumServerError> {
+    let (revision, _) = JobRevision::Branch(branch).to_revision_and_ref();
+    let commit_hash = git::resolve_revision(Path::new(&state.build_cache_location()), repo.repo_url.clone(), repo.repo_name.clone(), revision).await?;
+
+    read_pipeline(repo.repo_url.clone(), repo.repo_name.clone(), commit_hash, state).await
+}
+
+pub(super) async fn read_pipeline(html_url: String, name: String, commit_hash: String, state: &ConstructumServerState) -> Result<Pipeline, ConstructumServerError> {
     let mut pipeline_file = git::get_pipeline_file(
         Path::new(&state.build_cache_location()),
-        payload.html_url.clone(),
-        payload.name.clone(),
-        payload.commit_hash.clone(),
+        html_url,
+        name,
+        commit_hash,
     )
     .await?;
     let mut pipeline_contents = String::new();
@@ -49,93 +200,176 @@ async fn record_new_job_to_sql(payload: CreateJobPayload, state: ConstructumServ
 
     let mut pipeline: Pipeline = serde_yaml::from_str(&pipeline_contents)?;
     pipeline.normalize();
-    println!("{pipeline:?}");
 
-    let pipeline_uuid = Uuid::new_v4();
-    
-    super::api::job::db::create_job(state.postgres(), pipeline_uuid, repo_ref.builds_executed+1, repo_ref.repo_uuid, payload).await?;
-    super::api::repo::db::update_repo_seq(state.postgres(), repo_ref.repo_uuid, repo_ref.builds_executed+1).await?;
+    Ok(pipeline)
+}
+
+// None means "unknown", which runs every step
+async fn resolve_changed_files(payload: &CreateJobPayload, state: &ConstructumServerState) -> Result<Option<Vec<String>>, ConstructumServerError> {
+    let diffed = match &payload.before {
+        Some(before) => git::changed_files(Path::new(&state.build_cache_location()), payload.name.clone(), before.clone(), payload.commit_hash.clone()).await?,
+        None => None,
+    };
 
-    Ok(pipeline_uuid)
+    Ok(match diffed {
+        Some(files) => Some(files),
+        None if !payload.commit_files.is_empty() => Some(payload.commit_files.clone()),
+        None => None,
+    })
 }
 
-async fn assign_job_to_k8s(pipeline_uuid: Uuid, state: ConstructumServerState) -> Result<(), ConstructumServerError> {
-    let pipeline_name = format!("pipeline-{pipeline_uuid}");
-    let pipeline_client_name = format!("{pipeline_name}-client");
+pub(super) async fn assign_job_to_k8s(pipeline_uuid: Uuid, state: ConstructumServerState) -> Result<(), ConstructumServerError> {
+    let job_info = super::api::job::db::get_job(pipeline_uuid, state.postgres()).await?;
+    let repo_info = super::api::repo::db::get_repo(job_info.repo_id, state.postgres()).await?;
+    let identity = ResourceIdentity::new(&job_info, &repo_info);
+    let workspace_uuid = match job_info.workspace_mode(&repo_info) {
+        WorkspaceMode::Volume => Some(job_info.workspace_uuid()),
+        // the client pod brings its own workspace
+        WorkspaceMode::Pod => None,
+    };
+    let settings = state.kube_settings();
 
-    // create PVC on server process
-    let k8s_client = kube::Client::try_default().await?;
-    let pvcs: Api<PersistentVolumeClaim> = Api::namespaced(k8s_client, "constructum");
-    let pvc_data = build_client_pvc(pipeline_uuid)?;
-    pvcs.create(&PostParams::default(), &pvc_data).await?;
+    // create PVC on server process, unless the job resumes in the workspace of an earlier run
+    if workspace_uuid == Some(pipeline_uuid) {
+        // repos too large for the default workspace, or on other storage, bring their own
+        let storage_class = repo_info.storage_class.as_deref().unwrap_or(&settings.storage_class);
+        let workspace_size = repo_info.workspace_size.as_deref().unwrap_or(&settings.workspace_size);
+
+        let k8s_client = kube::Client::try_default().await?;
+        let pvcs: Api<PersistentVolumeClaim> = Api::namespaced(k8s_client, &settings.namespace);
+        let pvc_data = build_client_pvc(&identity, &settings.namespace, storage_class, workspace_size)?;
+        pvcs.create(&PostParams::default(), &pvc_data).await?;
+    }
     // create client job
     let k8s_client = kube::Client::try_default().await?;
-    let jobs: Api<Job> = Api::namespaced(k8s_client, "constructum");
-    let data = crate::kube::build_client_job(pipeline_uuid, pipeline_client_name.clone(), state.container_name(), Some(String::from("constructum-client-validate")))?;
+    let jobs: Api<Job> = Api::namespaced(k8s_client, &settings.namespace);
+    let data = crate::kube::build_client_job(&identity, workspace_uuid, state.container_name(), settings)?;
     let _ = jobs.create(&PostParams::default(), &data).await?;
 
-    {
-        // only handle the lock here
-        state.current_jobs().write().expect("lock poisoned").insert(pipeline_uuid);
-    }
+    watch_job(pipeline_uuid, state);
+
+    Ok(())
+}
+
+// for when the client cannot finish the job itself, which includes closing its steps
+async fn fail_client_job(pipeline_uuid: Uuid, state: &ConstructumServerState) -> Result<(), ConstructumServerError> {
+    super::api::job::db::complete_job(state.postgres(), PipelineStatus::Failed, pipeline_uuid).await?;
+    super::api::step::db::finish_unfinished_steps(state.postgres(), pipeline_uuid, StepStatus::Fail).await?;
+    Ok(())
+}
+
+// follows a running client job until it finishes and cleans up after it
+pub(super) fn watch_job(pipeline_uuid: Uuid, state: ConstructumServerState) {
+    let pipeline_client_name = client_job_name(pipeline_uuid);
+
+    // only handle the lock here. it is held across the spawn so the task cannot remove itself before it is recorded
+    let jobs_lock = state.current_jobs();
+    let mut current_jobs = jobs_lock.write().expect("lock poisoned");
 
     // split this out to not block the response to the Git server
-    task::spawn(async move {
+    let handle = task::spawn(async move {
         server_job(pipeline_client_name, pipeline_uuid, state).await;
     });
-
-    Ok(())
+    current_jobs.insert(pipeline_uuid, handle);
 }
 
 async fn server_job(pipeline_client_name: String, pipeline_uuid: Uuid, state: ConstructumServerState) {
-    let k8s_client = kube::Client::try_default().await.expect("failed to acquire k8s client");
-    let jobs: Api<Job> = Api::namespaced(k8s_client, "constructum");
-    let logs_stream_fut = logs_to_redis(state.redis(), pipeline_client_name.clone(), format!("{pipeline_client_name}-container"), String::from("_client"));
+    if let Err(err) = report_job_status(pipeline_uuid, &state).await {
+        error!("Failed to report job status: {err}");
+    }
 
-    let job_done_fut = await_condition(jobs.clone(), &pipeline_client_name, conditions::Condition::or(conditions::is_job_completed(), crate::kube::utils::is_job_failed()));
+    let namespace = state.kube_settings().namespace.clone();
+    let logs_stream_fut = logs_to_redis(state.redis(), pipeline_uuid, String::from("_client"), pipeline_client_name.clone(), String::from(CLIENT_CONTAINER_NAME), namespace.clone());
     let log_stream_handle = tokio::spawn(logs_stream_fut);
 
-    let res = tokio::join!(
-        log_stream_handle,
-        job_done_fut
-    );
-
-    match res.0.expect("failed to join") {
-        Ok(_) => {},
-        Err(err) => error!("Failed: {err}"),
+    // a cancelled job has its client job deleted out from under us
+    match await_job(&pipeline_client_name, state.kube_settings()).await {
+        Ok(JobWaitOutcome::Stuck(reason)) => {
+            // the client never ran, so nothing else will finish the job
+            log_stream_handle.abort();
+            error!("Client job of {pipeline_uuid} cannot start: {reason}");
+            if let Err(err) = fail_client_job(pipeline_uuid, &state).await {
+                error!("Failed to fail job {pipeline_uuid}: {err}");
+            }
+        },
+        Ok(JobWaitOutcome::Finished | JobWaitOutcome::Deleted) => {
+            if let Err(err) = log_stream_handle.await.expect("failed to join") {
+                error!("Failed: {err}");
+            }
+        },
+        Err(err) => {
+            // nothing can tell when the client finishes any more, so the job is failed before its client is torn down
+            log_stream_handle.abort();
+            error!("Lost track of client job of {pipeline_uuid}, failing the job: {err}");
+            if let Err(err) = fail_client_job(pipeline_uuid, &state).await {
+                error!("Failed to fail job {pipeline_uuid}: {err}");
+            }
+        },
     }
 
-    match res.1 {
-        Ok(_) => {},
-        Err(err) => error!("Failed: {err}"),
+    // cancellation has already torn everything down and reported the status
+    let job_info = match super::api::job::db::get_job(pipeline_uuid, state.postgres()).await {
+        Ok(job_info) if matches!(job_info.status, PipelineStatus::Cancelled | PipelineStatus::TimedOut) => {
+            state.current_jobs().write().expect("lock poisoned").remove(&pipeline_uuid);
+            if let Err(err) = super::dispatch_queued_jobs(state).await {
+                error!("Failed to dispatch queued jobs: {err}");
+            }
+            return;
+        },
+        Ok(job_info) => Some(job_info),
+        Err(err) => {
+            error!("Failed to look up job {pipeline_uuid}: {err}");
+            None
+        },
     };
 
-    // TODO: check for job cancellation and set job status correctly
-
     // record results
-    match put_pod_logs_to_s3(pipeline_client_name.clone(), None, pipeline_client_name.to_string(), state.s3_bucket()).await {
-        Ok(_) => {},
-        Err(e) => {
-            println!("{e}");
-        }
-    };
+    if let Err(err) = put_pod_logs_to_s3(pipeline_client_name.clone(), None, pipeline_client_name.to_string(), state.s3_bucket(), &namespace).await {
+        error!("Failed to archive client logs of {pipeline_uuid}: {err}");
+    }
 
     // clean up client job
-    delete_job(&pipeline_client_name).await.expect("failed to delete job");
-    delete_pvc(&pipeline_uuid.to_string()).await.expect("failed to delete job");
+    // leftovers are picked up by the reconciler, the job still has to leave current_jobs
+    if let Err(err) = delete_job(&pipeline_client_name, &namespace).await {
+        error!("Failed to delete client job of {pipeline_uuid}: {err}");
+    }
+    // the workspace of a failed job is kept so the job can be resumed from the failed step
+    let workspace_uuid = match job_info {
+        Some(job_info) if job_info.status == PipelineStatus::Failed => None,
+        Some(job_info) => Some(job_info.workspace_uuid()),
+        None => Some(pipeline_uuid),
+    };
+    if let Some(workspace_uuid) = workspace_uuid {
+        if let Err(err) = delete_pvc(&workspace_uuid.to_string(), &namespace).await {
+            error!("Failed to delete workspace of {pipeline_uuid}: {err}");
+        }
+    }
 
     state.current_jobs().write().expect("lock poisoned").remove(&pipeline_uuid);
-}
 
-pub async fn restart_unfinished_jobs(state: ConstructumServerState) -> Result<(), ConstructumServerError> {
-    let unfinished_jobs = list_unfinished_jobs(state.postgres()).await?;
+    if let Err(err) = report_job_status(pipeline_uuid, &state).await {
+        error!("Failed to report job status: {err}");
+    }
 
-    // restart first N jobs, where N is defined by TODO: config
-    for unfinished in unfinished_jobs {
-        if !state.current_jobs().read().expect("lock poisoned").contains(&unfinished.job_uuid) {
-            assign_job_to_k8s(unfinished.job_uuid, state.clone()).await?;
-            break;
-        }
+    if let Err(err) = super::trigger_downstream_jobs(pipeline_uuid, &state).await {
+        error!("Failed to trigger downstream jobs: {err}");
     }
-    Ok(())
-}
\ No newline at end of file
+
+    // this job's slot is free now
+    if let Err(err) = super::dispatch_queued_jobs(state).await {
+        error!("Failed to dispatch queued jobs: {err}");
+    }
+}
+
+pub(super) async fn report_job_status(pipeline_uuid: Uuid, state: &ConstructumServerState) -> Result<(), ConstructumServerError> {
+    // status reporting is opt-in; without a server token we have no identity to post as
+    let token = match state.git_server_token() {
+        Some(token) => token,
+        None => return Ok(()),
+    };
+
+    let job = super::api::job::db::get_job(pipeline_uuid, state.postgres()).await?;
+    let repo = super::api::repo::db::get_repo(job.repo_id, state.postgres()).await?;
+
+    super::api::repo::system::report_commit_status(state.git_forge(), state.git_server_url(), &repo, &job.commit_id, job.status, token).await
+}
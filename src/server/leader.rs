@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use crate::{kube::try_acquire_lease, ConstructumServerState};
+
+const LEASE_NAME: &str = "constructum-server-leader";
+const LEASE_DURATION_SECS: i32 = 15;
+// well inside the lease duration so a healthy leader never lets it lapse
+const LEASE_RENEW_INTERVAL_SECS: u64 = 5;
+
+// keeps trying to take or renew the leader lease for as long as the server runs.
+// the leader is the only replica that dispatches, watches and reconciles jobs and runs schedules.
+pub async fn run_leader_election(state: ConstructumServerState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(LEASE_RENEW_INTERVAL_SECS));
+
+    loop {
+        interval.tick().await;
+
+        let leading = match try_acquire_lease(LEASE_NAME, &state.identity(), LEASE_DURATION_SECS, &state.kube_settings().namespace).await {
+            Ok(leading) => leading,
+            Err(err) => {
+                // if we cannot confirm the lease we have to assume someone else may take it over
+                error!("Failed to renew leader lease: {err}");
+                false
+            }
+        };
+
+        let was_leading = state.set_leader(leading);
+        if leading && !was_leading {
+            info!("{} became the leader", state.identity());
+            // pick up the jobs the previous leader was watching
+            if let Err(err) = super::reconcile_jobs(state.clone()).await {
+                error!("Failed to reconcile jobs: {err}");
+            }
+            if let Err(err) = super::dispatch_queued_jobs(state.clone()).await {
+                error!("Failed to dispatch queued jobs: {err}");
+            }
+        } else if !leading && was_leading {
+            warn!("{} lost the leader lease, handing its jobs to the next leader", state.identity());
+            stop_watching_jobs(&state);
+        }
+    }
+}
+
+// the new leader re-attaches to these jobs when it reconciles
+fn stop_watching_jobs(state: &ConstructumServerState) {
+    let watchers: Vec<_> = state.current_jobs().write().expect("lock poisoned").drain().collect();
+    for (_, handle) in watchers {
+        handle.abort();
+    }
+}
@@ -1,5 +1,23 @@
 pub(crate) mod error;
 mod job_spawning;
+mod cancellation;
+mod downstream;
+mod leader;
+mod pipeline_run;
+mod queue;
+mod reconcile;
+mod rerun;
+mod scheduler;
+mod watchdog;
 pub mod api;
 
-pub use self::job_spawning::*;
\ No newline at end of file
+pub use self::job_spawning::*;
+pub use self::cancellation::*;
+pub use self::downstream::*;
+pub use self::leader::*;
+pub use self::pipeline_run::*;
+pub use self::queue::*;
+pub use self::reconcile::*;
+pub use self::rerun::*;
+pub use self::scheduler::*;
+pub use self::watchdog::*;
@@ -0,0 +1,177 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::StreamExt;
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use kube::{api::{ListParams, Patch, PatchParams}, runtime::{controller::{Action, Controller}, finalizer::{finalizer, Error as FinalizerError, Event}}, Api, CustomResourceExt, ResourceExt};
+use serde_json::json;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{kube::{PipelineRun, PipelineRunStatus, PIPELINE_RUN_FINALIZER}, ConstructumServerState};
+
+use super::{api::{job::{self, JobInfo, JobTrigger}, repo}, error::ConstructumServerError, job_spawning::{create_job, payload_for_revision, JobRevision}};
+
+// how often a run whose job has not finished gets its status refreshed
+pub const PIPELINE_RUN_POLL_SECS: u64 = 10;
+
+// replicas that do not hold the lease check back this often, and failed reconciles are retried after it
+const PIPELINE_RUN_RETRY_SECS: u64 = 30;
+
+struct PipelineRunContext {
+    runs: Api<PipelineRun>,
+    state: ConstructumServerState,
+}
+
+pub fn pipeline_run_crd() -> CustomResourceDefinition {
+    PipelineRun::crd()
+}
+
+// turns PipelineRuns in the server namespace into jobs and mirrors the job status back onto them.
+// runs until the watch ends, which only happens when the process shuts down.
+pub async fn run_pipeline_run_controller(state: ConstructumServerState) {
+    let k8s_client = match kube::Client::try_default().await {
+        Ok(k8s_client) => k8s_client,
+        Err(err) => {
+            error!("Failed to start PipelineRun controller: {err}");
+            return;
+        }
+    };
+    let runs: Api<PipelineRun> = Api::namespaced(k8s_client, &state.kube_settings().namespace);
+    let context = Arc::new(PipelineRunContext { runs: runs.clone(), state });
+
+    Controller::new(runs, ListParams::default())
+        .run(reconcile_pipeline_run, retry_pipeline_run, context)
+        .for_each(|result| async move {
+            if let Err(err) = result {
+                warn!("Failed to reconcile PipelineRun: {err}");
+            }
+        })
+        .await;
+}
+
+async fn reconcile_pipeline_run(run: Arc<PipelineRun>, context: Arc<PipelineRunContext>) -> Result<Action, FinalizerError<ConstructumServerError>> {
+    if !context.state.is_leader() {
+        return Ok(Action::requeue(Duration::from_secs(PIPELINE_RUN_RETRY_SECS)));
+    }
+
+    finalizer(&context.runs, PIPELINE_RUN_FINALIZER, run, |event| async {
+        match event {
+            Event::Apply(run) => apply_pipeline_run(&run, &context).await,
+            Event::Cleanup(run) => clean_up_pipeline_run(&run, &context).await,
+        }
+    }).await
+}
+
+fn retry_pipeline_run(_run: Arc<PipelineRun>, _err: &FinalizerError<ConstructumServerError>, _context: Arc<PipelineRunContext>) -> Action {
+    Action::requeue(Duration::from_secs(PIPELINE_RUN_RETRY_SECS))
+}
+
+async fn apply_pipeline_run(run: &PipelineRun, context: &PipelineRunContext) -> Result<Action, ConstructumServerError> {
+    let state = &context.state;
+    let run_uid = pipeline_run_uid(run)?;
+
+    // the job is looked up by the run's uid, so a run never starts a second job
+    let job_info = match job::db::get_job_for_pipeline_run(state.postgres(), run_uid).await? {
+        Some(job_info) => job_info,
+        None if was_rejected(run) => return Ok(Action::await_change()),
+        None => match start_pipeline_run(run, run_uid, state).await {
+            Ok(Some(job_uuid)) => job::db::get_job(job_uuid, state.postgres()).await?,
+            Ok(None) => {
+                let status = rejected_status(run, "Skipped", String::from("the pipeline's path filters exclude this commit"));
+                update_status(&context.runs, run, status).await?;
+                return Ok(Action::await_change());
+            },
+            Err(err) if is_rejection(&err) => {
+                update_status(&context.runs, run, rejected_status(run, "Failed", err.to_string())).await?;
+                return Ok(Action::await_change());
+            },
+            Err(err) => return Err(err),
+        },
+    };
+
+    update_status(&context.runs, run, job_status(run, &job_info)).await?;
+
+    if job_info.is_finished {
+        Ok(Action::await_change())
+    } else {
+        Ok(Action::requeue(Duration::from_secs(PIPELINE_RUN_POLL_SECS)))
+    }
+}
+
+// a deleted run takes its job down with it
+async fn clean_up_pipeline_run(run: &PipelineRun, context: &PipelineRunContext) -> Result<Action, ConstructumServerError> {
+    let run_uid = pipeline_run_uid(run)?;
+
+    if let Some(job_info) = job::db::get_job_for_pipeline_run(context.state.postgres(), run_uid).await?.filter(|x| !x.is_finished) {
+        info!("PipelineRun {} was deleted, cancelling job {}", run.name_any(), job_info.job_uuid);
+        super::cancel_job(job_info.job_uuid, &context.state).await?;
+    }
+
+    Ok(Action::await_change())
+}
+
+async fn start_pipeline_run(run: &PipelineRun, run_uid: Uuid, state: &ConstructumServerState) -> Result<Option<Uuid>, ConstructumServerError> {
+    let (repo_owner, repo_name) = repo::split_repo_path(&run.spec.repo)
+        .ok_or_else(|| ConstructumServerError::InvalidJobRequest(format!("repo {} is not of the form owner/name", run.spec.repo)))?;
+    let repo_info = repo::db::get_repo_by_name(repo_owner, repo_name, state.postgres()).await?
+        .ok_or(ConstructumServerError::NoRepoFound)?;
+
+    let mut payload = payload_for_revision(repo_info, JobRevision::Commit(run.spec.commit.clone()), JobTrigger::PipelineRun, run.spec.parameters.clone(), state).await?;
+    payload.pipeline_run = Some(run_uid);
+
+    info!("starting a job for PipelineRun {}", run.name_any());
+    create_job(payload, state.clone()).await
+}
+
+fn pipeline_run_uid(run: &PipelineRun) -> Result<Uuid, ConstructumServerError> {
+    run.uid().and_then(|x| Uuid::parse_str(&x).ok())
+        .ok_or_else(|| ConstructumServerError::InvalidJobRequest(format!("PipelineRun {} has no uid", run.name_any())))
+}
+
+// errors that retrying will not fix until someone changes the run or the repository
+fn is_rejection(err: &ConstructumServerError) -> bool {
+    matches!(err,
+        ConstructumServerError::NoRepoFound
+        | ConstructumServerError::InvalidJobRequest(_)
+        | ConstructumServerError::InvalidParameters(_)
+        | ConstructumServerError::InvalidPathFilter(_)
+        | ConstructumServerError::YAMLDeserialize(_)
+    )
+}
+
+// rejected runs are tried again once their spec changes
+fn was_rejected(run: &PipelineRun) -> bool {
+    run.status.as_ref()
+        .map(|x| x.job.is_none() && x.phase.is_some() && x.observed_generation == run.metadata.generation)
+        .unwrap_or(false)
+}
+
+fn rejected_status(run: &PipelineRun, phase: &str, reason: String) -> PipelineRunStatus {
+    PipelineRunStatus {
+        phase: Some(String::from(phase)),
+        reason: Some(reason),
+        observed_generation: run.metadata.generation,
+        ..Default::default()
+    }
+}
+
+fn job_status(run: &PipelineRun, job_info: &JobInfo) -> PipelineRunStatus {
+    PipelineRunStatus {
+        job: Some(job_info.job_uuid.to_string()),
+        build_number: Some(job_info.job_number),
+        phase: Some(String::from(Into::<&str>::into(job_info.status))),
+        reason: job_info.status_reason.clone(),
+        started_at: job_info.started_at.map(|x| x.to_rfc3339()),
+        finished_at: job_info.finished_at.map(|x| x.to_rfc3339()),
+        observed_generation: run.metadata.generation,
+    }
+}
+
+async fn update_status(runs: &Api<PipelineRun>, run: &PipelineRun, status: PipelineRunStatus) -> Result<(), ConstructumServerError> {
+    if run.status.as_ref() == Some(&status) {
+        return Ok(());
+    }
+
+    runs.patch_status(&run.name_any(), &PatchParams::default(), &Patch::Merge(json!({ "status": status }))).await?;
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use futures::future::BoxFuture;
+use tracing::{error, info};
+
+use crate::{pipeline::PipelineStatus, ConstructumServerState};
+
+use super::{api::job, error::ConstructumServerError, job_spawning::{assign_job_to_k8s, report_job_status}};
+
+// how often the server checks for queued jobs that can start, on top of the dispatch after every job change
+pub const QUEUE_POLL_INTERVAL_SECS: u64 = 10;
+
+// starts as many queued jobs as the concurrency limits allow.
+// boxed because the jobs this starts spawn their own server_job, which calls back into here when it finishes.
+pub fn dispatch_queued_jobs(state: ConstructumServerState) -> BoxFuture<'static, Result<(), ConstructumServerError>> {
+    Box::pin(async move {
+        // only the leader starts jobs, so only the leader ends up watching them
+        if !state.is_leader() {
+            return Ok(());
+        }
+
+        let claimed = job::db::claim_queued_jobs(
+            state.postgres(),
+            i64::from(state.max_concurrent_jobs()),
+            i64::from(state.max_concurrent_jobs_per_repo()),
+        ).await?;
+
+        for job_uuid in claimed {
+            info!("dispatching job {job_uuid}");
+            if let Err(err) = assign_job_to_k8s(job_uuid, state.clone()).await {
+                // the job was already claimed, so leaving it as is would hold its slot forever
+                error!("Failed to start job {job_uuid}: {err}");
+                // the rest of the batch is claimed too and must still start
+                if let Err(err) = job::db::complete_job(state.postgres(), PipelineStatus::Failed, job_uuid).await {
+                    error!("Failed to fail job {job_uuid}: {err}");
+                    continue;
+                }
+                if let Err(err) = report_job_status(job_uuid, &state).await {
+                    error!("Failed to report job status: {err}");
+                }
+            }
+        }
+
+        Ok(())
+    })
+}
@@ -0,0 +1,112 @@
+use std::{collections::{BTreeMap, HashSet}, str::FromStr};
+
+use chrono::{Duration, Utc};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{kube::{delete_job, delete_pipeline_jobs, delete_pvc, list_pipeline_jobs, list_pipeline_pvcs, COMPONENT_CLIENT, LABEL_COMPONENT, LABEL_JOB}, pipeline::PipelineStatus, ConstructumServerState};
+
+use super::{api::{job, step::{self, model::StepStatus}}, error::ConstructumServerError, job_spawning::{report_job_status, watch_job}};
+
+// how often the server compares the jobs table against what is actually in the cluster
+pub const RECONCILE_INTERVAL_SECS: u64 = 120;
+
+// a claimed job gets this long to have its client job created before it counts as vanished
+const DISPATCH_GRACE_SECS: i64 = 120;
+
+// brings the jobs table and the cluster back in line after server restarts or lost pods:
+// running jobs nobody is watching get a watcher again, jobs whose client job disappeared are failed,
+// and Jobs and PVCs that no longer belong to an unfinished job are deleted.
+pub async fn reconcile_jobs(state: ConstructumServerState) -> Result<(), ConstructumServerError> {
+    if !state.is_leader() {
+        return Ok(());
+    }
+
+    let namespace = &state.kube_settings().namespace;
+    let cluster_jobs = list_pipeline_jobs(namespace).await?;
+    let cluster_pvcs = list_pipeline_pvcs(namespace).await?;
+    let unfinished = job::db::list_unfinished_jobs(state.postgres()).await?;
+    let watched: HashSet<Uuid> = state.current_jobs().read().expect("lock poisoned").keys().copied().collect();
+
+    for job_info in unfinished.iter().filter(|x| x.status == PipelineStatus::InProgress && !watched.contains(&x.job_uuid)) {
+        let is_client_job = |labels: Option<&BTreeMap<String, String>>| {
+            pipeline_label(labels) == Some(job_info.job_uuid) && labels.and_then(|x| x.get(LABEL_COMPONENT)).map(String::as_str) == Some(COMPONENT_CLIENT)
+        };
+
+        if cluster_jobs.iter().any(|x| is_client_job(x.metadata.labels.as_ref())) {
+            info!("re-attaching to job {}", job_info.job_uuid);
+            watch_job(job_info.job_uuid, state.clone());
+            continue;
+        }
+
+        let dispatch_deadline = Utc::now() - Duration::seconds(DISPATCH_GRACE_SECS);
+        if job_info.started_at.map(|x| x > dispatch_deadline).unwrap_or(false) {
+            continue;
+        }
+
+        warn!("client job of {} vanished, failing the job", job_info.job_uuid);
+        if let Err(err) = fail_vanished_job(job_info.job_uuid, &state).await {
+            error!("Failed to fail vanished job {}: {err}", job_info.job_uuid);
+        }
+    }
+
+    // anything an unfinished or watched job runs in stays, as do the workspaces of recently failed jobs
+    let mut in_use: HashSet<Uuid> = unfinished.iter().flat_map(|x| [x.job_uuid, x.workspace_uuid()]).collect();
+    in_use.extend(watched);
+    let retained: HashSet<Uuid> = job::db::list_retained_workspaces(state.postgres(), state.workspace_retention_hours() as i32).await?.into_iter().collect();
+
+    for cluster_job in cluster_jobs {
+        let pipeline_uuid = match pipeline_label(cluster_job.metadata.labels.as_ref()) {
+            Some(pipeline_uuid) => pipeline_uuid,
+            None => continue,
+        };
+
+        if in_use.contains(&pipeline_uuid) {
+            continue;
+        }
+
+        if let Some(name) = cluster_job.metadata.name {
+            info!("deleting orphaned job {name}");
+            if let Err(err) = delete_job(&name, namespace).await {
+                error!("Failed to delete orphaned job {name}: {err}");
+            }
+        }
+    }
+
+    for cluster_pvc in cluster_pvcs {
+        let pipeline_uuid = match pipeline_label(cluster_pvc.metadata.labels.as_ref()) {
+            Some(pipeline_uuid) => pipeline_uuid,
+            None => continue,
+        };
+
+        if in_use.contains(&pipeline_uuid) || retained.contains(&pipeline_uuid) {
+            continue;
+        }
+
+        info!("deleting orphaned workspace of {pipeline_uuid}");
+        if let Err(err) = delete_pvc(&pipeline_uuid.to_string(), namespace).await {
+            error!("Failed to delete orphaned workspace of {pipeline_uuid}: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+async fn fail_vanished_job(job_uuid: Uuid, state: &ConstructumServerState) -> Result<(), ConstructumServerError> {
+    job::db::complete_job(state.postgres(), PipelineStatus::Failed, job_uuid).await?;
+    step::db::finish_unfinished_steps(state.postgres(), job_uuid, StepStatus::Fail).await?;
+    // the workspace is kept like for any other failed job
+    delete_pipeline_jobs(&job_uuid.to_string(), &state.kube_settings().namespace).await?;
+
+    if let Err(err) = report_job_status(job_uuid, state).await {
+        error!("Failed to report job status: {err}");
+    }
+
+    Ok(())
+}
+
+fn pipeline_label(labels: Option<&BTreeMap<String, String>>) -> Option<Uuid> {
+    labels
+        .and_then(|x| x.get(LABEL_JOB))
+        .and_then(|x| Uuid::from_str(x).ok())
+}
@@ -0,0 +1,55 @@
+use uuid::Uuid;
+
+use crate::{kube::pvc_exists, pipeline::PipelineStatus, ConstructumServerState};
+
+use super::{api::{job::{self, JobInfo, JobTrigger}, repo, step::{self, model::StepStatus}}, error::ConstructumServerError, create_job, CreateJobPayload};
+
+// starts a fresh run of a finished job on the same commit, with the same parameters
+pub async fn rerun_job(original: JobInfo, state: ConstructumServerState) -> Result<Uuid, ConstructumServerError> {
+    if !original.is_finished {
+        return Err(ConstructumServerError::JobNotFinished);
+    }
+
+    let payload = rerun_payload(&original, &state).await?;
+
+    create_job(payload, state).await?
+        .ok_or_else(|| ConstructumServerError::InvalidJobRequest(String::from("re-run was not started")))
+}
+
+// starts a run of a failed job that picks up at the first failed step, in the original's workspace
+pub async fn resume_job(original: JobInfo, state: ConstructumServerState) -> Result<Uuid, ConstructumServerError> {
+    if original.status != PipelineStatus::Failed {
+        return Err(ConstructumServerError::NotResumable(String::from("only failed jobs can be resumed")));
+    }
+
+    let steps = step::db::list_steps_for_job(state.postgres(), original.job_uuid).await?;
+    let failed_step = steps.iter()
+        .find(|x| x.status == StepStatus::Fail)
+        .ok_or_else(|| ConstructumServerError::NotResumable(String::from("the job failed before any of its steps")))?;
+
+    // the artifacts of the earlier steps only exist as long as the workspace does,
+    // and two runs in one workspace would trample each other
+    let workspace = original.workspace_uuid();
+    if !pvc_exists(&workspace.to_string(), &state.kube_settings().namespace).await? {
+        return Err(ConstructumServerError::NotResumable(String::from("the workspace of the job no longer exists")));
+    }
+    if !job::db::list_unfinished_jobs_in_workspace(workspace, state.postgres()).await?.is_empty() {
+        return Err(ConstructumServerError::NotResumable(String::from("another run is already using the workspace of the job")));
+    }
+
+    let mut payload = rerun_payload(&original, &state).await?;
+    payload.resume_from_step = Some(failed_step.step_number);
+    payload.workspace = Some(workspace);
+
+    create_job(payload, state).await?
+        .ok_or_else(|| ConstructumServerError::InvalidJobRequest(String::from("resumed run was not started")))
+}
+
+async fn rerun_payload(original: &JobInfo, state: &ConstructumServerState) -> Result<CreateJobPayload, ConstructumServerError> {
+    let repo_info = repo::db::get_repo(original.repo_id, state.postgres()).await?;
+
+    let mut payload = CreateJobPayload::new(repo_info.git_id, repo_info.repo_url, repo_info.repo_name, original.commit_id.clone(), original.git_ref.clone(), JobTrigger::Rerun);
+    payload.rerun_of = Some(original.job_uuid);
+
+    Ok(payload)
+}
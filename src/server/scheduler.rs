@@ -0,0 +1,44 @@
+use chrono::Utc;
+use tracing::{error, info};
+
+use crate::ConstructumServerState;
+
+use super::{api::{job::JobTrigger, repo, schedule}, error::ConstructumServerError, create_job_for_revision, JobRevision};
+
+// how often the server checks for schedules that are due
+pub const SCHEDULE_POLL_INTERVAL_SECS: u64 = 30;
+
+pub async fn run_due_schedules(state: ConstructumServerState) -> Result<(), ConstructumServerError> {
+    if !state.is_leader() {
+        return Ok(());
+    }
+
+    let due_schedules = schedule::db::list_due_schedules(state.postgres()).await?;
+
+    for due in due_schedules {
+        let next_run = match schedule::parse_cron_expression(&due.cron_expression).ok().and_then(|x| schedule::next_run_after(&x, Utc::now())) {
+            Some(next_run) => next_run,
+            None => {
+                error!("schedule {} has an invalid cron expression: {}", due.schedule_uuid, due.cron_expression);
+                continue;
+            }
+        };
+
+        // another server may have picked this run up in the meantime
+        if !schedule::db::claim_schedule_run(state.postgres(), due.schedule_uuid, due.next_run, next_run).await? {
+            continue;
+        }
+
+        let repo_info = repo::db::get_repo(due.repo_id, state.postgres()).await?;
+        match create_job_for_revision(repo_info, JobRevision::Branch(due.branch.clone()), JobTrigger::Cron, due.parameters.clone(), state.clone()).await {
+            Ok(Some(job_uuid)) => {
+                info!("schedule {} started job {job_uuid}", due.schedule_uuid);
+                schedule::db::record_schedule_job(state.postgres(), due.schedule_uuid, job_uuid).await?;
+            },
+            Ok(None) => info!("schedule {} did not start a job", due.schedule_uuid),
+            Err(err) => error!("schedule {} failed to start a job: {err}", due.schedule_uuid),
+        }
+    }
+
+    Ok(())
+}
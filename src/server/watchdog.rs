@@ -0,0 +1,38 @@
+use tracing::{error, warn};
+
+use crate::ConstructumServerState;
+
+use super::{api::{job, step::model::StepStatus}, cancellation::tear_down_job, error::ConstructumServerError};
+
+// how often the server looks for jobs that ran past their deadline
+pub const WATCHDOG_INTERVAL_SECS: u64 = 30;
+
+// marks running jobs that exceeded their deadline TimedOut and tears them down.
+// this catches jobs whose client is wedged, which server_job would otherwise wait on forever.
+pub async fn time_out_expired_jobs(state: ConstructumServerState) -> Result<(), ConstructumServerError> {
+    if !state.is_leader() {
+        return Ok(());
+    }
+
+    let max_minutes = i32::try_from(state.max_job_duration_minutes()).unwrap_or(i32::MAX);
+    let expired = job::db::list_expired_jobs(state.postgres(), max_minutes).await?;
+    if expired.is_empty() {
+        return Ok(());
+    }
+
+    for job_info in expired {
+        let reason = format!("exceeded its deadline of {} minutes", job_info.deadline_minutes(max_minutes));
+        // the job may have finished since it was listed
+        if !job::db::time_out_job(state.postgres(), job_info.job_uuid, reason.clone()).await? {
+            continue;
+        }
+
+        warn!("job {} {reason}", job_info.job_uuid);
+        if let Err(err) = tear_down_job(job_info.job_uuid, StepStatus::Fail, &state).await {
+            error!("Failed to tear down timed out job {}: {err}", job_info.job_uuid);
+        }
+    }
+
+    // the timed out jobs held slots that queued jobs can have now
+    super::dispatch_queued_jobs(state).await
+}
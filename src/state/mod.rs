@@ -3,6 +3,8 @@ mod server;
 
 use crate::config::Config;
 use crate::config::ConstructumConfigError;
+use crate::kube::KubeSettings;
+use crate::redis::LiveLogs;
 
 pub use self::client::*;
 pub use self::server::*;
@@ -14,13 +16,14 @@ use sqlx::{Pool, Postgres};
 pub struct ConstructumSharedState {
     postgres: Pool<Postgres>,
     s3_bucket: Bucket,
-    redis: redis::Client,
+    redis: LiveLogs,
     container_name: String,
+    kube_settings: KubeSettings,
 }
 
 impl ConstructumSharedState {
-    pub fn new(pool: Pool<Postgres>, s3_bucket: Bucket, redis_client: redis::Client, container_name: String) -> ConstructumSharedState {
-        ConstructumSharedState { postgres: pool, s3_bucket, redis: redis_client, container_name }
+    pub fn new(pool: Pool<Postgres>, s3_bucket: Bucket, redis: LiveLogs, container_name: String, kube_settings: KubeSettings) -> ConstructumSharedState {
+        ConstructumSharedState { postgres: pool, s3_bucket, redis, container_name, kube_settings }
     }
 
     pub async fn from(config: &Config) -> Result<ConstructumSharedState, ConstructumConfigError> {
@@ -29,8 +32,9 @@ impl ConstructumSharedState {
         Ok(ConstructumSharedState {
             postgres: pool,
             s3_bucket: bucket,
-            redis: redis_client,
+            redis: LiveLogs::new(redis_client, config),
             container_name: config.container_name.clone(),
+            kube_settings: KubeSettings::from(config),
         })
     }
     
@@ -46,7 +50,11 @@ impl ConstructumSharedState {
         self.container_name.clone()
     }
 
-    pub fn redis(&self) -> redis::Client {
+    pub fn redis(&self) -> LiveLogs {
         self.redis.clone()
     }
+
+    pub fn kube_settings(&self) -> &KubeSettings {
+        &self.kube_settings
+    }
 }
\ No newline at end of file
@@ -1,15 +1,30 @@
-use std::{sync::{Arc, RwLock}, collections::HashSet, ops::Deref};
+use std::{sync::{Arc, RwLock, atomic::{AtomicBool, Ordering}}, collections::HashMap, ops::Deref};
+
+use tokio::task::JoinHandle;
+use tracing::warn;
 
 use uuid::Uuid;
 
-use crate::{ConstructumSharedState, config::{Config, ConstructumConfigError}};
+use crate::{ConstructumSharedState, config::{Config, ConstructumConfigError, GitForge}};
 
 
 #[derive(Clone)]
 pub struct ConstructumServerState {
     git_server_url: String,
+    git_forge: GitForge,
+    git_server_token: Option<String>,
+    webhook_secret: Option<String>,
+    max_concurrent_jobs: u32,
+    max_concurrent_jobs_per_repo: u32,
+    workspace_retention_hours: u32,
+    max_job_duration_minutes: u32,
     build_cache_location: String,
-    current_jobs: Arc<RwLock<HashSet<Uuid>>>,
+    leader_election: bool,
+    pipeline_run_controller: bool,
+    identity: String,
+    is_leader: Arc<AtomicBool>,
+    // the server_job watching each job this process is following
+    current_jobs: Arc<RwLock<HashMap<Uuid, JoinHandle<()>>>>,
     shared: ConstructumSharedState,
 }
 
@@ -23,15 +38,86 @@ impl ConstructumServerState {
         .expect("failed to find git server URL");
 
         let bcl = config.build_cache_location.clone().expect("failed to find build cache location");
+        let leader_election = config.leader_election.unwrap_or(false);
+        let git_forge = config.git_forge.unwrap_or_default();
+        if git_forge == GitForge::Gitlab && config.webhook_secret.is_none() {
+            warn!("no webhook_secret is configured, GitLab webhooks will be refused");
+        }
 
-        Ok(ConstructumServerState { shared: css, git_server_url: gsu, build_cache_location: bcl, current_jobs: Arc::new(RwLock::new(HashSet::new())) })
+        Ok(ConstructumServerState {
+            shared: css,
+            git_server_url: gsu,
+            git_forge,
+            git_server_token: config.git_server_token.clone(),
+            webhook_secret: config.webhook_secret.clone(),
+            max_concurrent_jobs: config.max_concurrent_jobs.unwrap_or(5),
+            max_concurrent_jobs_per_repo: config.max_concurrent_jobs_per_repo.unwrap_or(2),
+            workspace_retention_hours: config.workspace_retention_hours.unwrap_or(24),
+            max_job_duration_minutes: config.max_job_duration_minutes.unwrap_or(360),
+            build_cache_location: bcl,
+            leader_election,
+            pipeline_run_controller: config.pipeline_run_controller.unwrap_or(false),
+            identity: config.pod_name.clone().unwrap_or_else(|| Uuid::new_v4().to_string()),
+            // without leader election this is the only replica, so it always leads
+            is_leader: Arc::new(AtomicBool::new(!leader_election)),
+            current_jobs: Arc::new(RwLock::new(HashMap::new()))
+        })
     }
 
     pub fn git_server_url(&self) -> String {
         self.git_server_url.clone()
     }
 
-    pub fn current_jobs(&self) -> Arc<RwLock<HashSet<Uuid>>> {
+    pub fn git_forge(&self) -> GitForge {
+        self.git_forge
+    }
+
+    pub fn git_server_token(&self) -> Option<String> {
+        self.git_server_token.clone()
+    }
+
+    pub fn webhook_secret(&self) -> Option<String> {
+        self.webhook_secret.clone()
+    }
+
+    pub fn max_concurrent_jobs(&self) -> u32 {
+        self.max_concurrent_jobs
+    }
+
+    pub fn max_concurrent_jobs_per_repo(&self) -> u32 {
+        self.max_concurrent_jobs_per_repo
+    }
+
+    pub fn workspace_retention_hours(&self) -> u32 {
+        self.workspace_retention_hours
+    }
+
+    pub fn max_job_duration_minutes(&self) -> u32 {
+        self.max_job_duration_minutes
+    }
+
+    pub fn leader_election(&self) -> bool {
+        self.leader_election
+    }
+
+    pub fn pipeline_run_controller(&self) -> bool {
+        self.pipeline_run_controller
+    }
+
+    pub fn identity(&self) -> String {
+        self.identity.clone()
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.is_leader.load(Ordering::SeqCst)
+    }
+
+    // returns whether this replica was the leader before
+    pub fn set_leader(&self, is_leader: bool) -> bool {
+        self.is_leader.swap(is_leader, Ordering::SeqCst)
+    }
+
+    pub fn current_jobs(&self) -> Arc<RwLock<HashMap<Uuid, JoinHandle<()>>>> {
         self.current_jobs.clone()
     }
 
@@ -46,4 +132,4 @@ impl Deref for ConstructumServerState {
     fn deref(&self) -> &Self::Target {
         &self.shared
     }
-}
\ No newline at end of file
+}